@@ -0,0 +1,237 @@
+//! Contains chart statistics for song select.
+
+use crate::{
+    chart::Chart,
+    clock::ChartClock,
+    note::{LaneKind, NoteKind},
+    time::{Instant, Seconds},
+    timeline::Timeline,
+};
+
+use num::rational::Ratio;
+
+/// Represents the number of notes by kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoteTotals {
+    pub tap: usize,
+    pub hold: usize,
+    pub mine: usize,
+    pub invisible: usize,
+    pub fake: usize,
+
+    /// Judged notes on scratch lanes, also counted as taps or holds.
+    pub scratch: usize,
+}
+
+impl NoteTotals {
+    /// Returns the number of judged notes.
+    pub fn judged(&self) -> usize {
+        self.tap + self.hold
+    }
+}
+
+/// Represents the densest window of judged notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeakWindow {
+    /// Elapsed seconds of the first note in the window.
+    pub start: Seconds,
+    pub notes: usize,
+
+    /// Notes per second over the window.
+    pub nps: Ratio<usize>,
+}
+
+/// Represents statistics of a chart. Only judged notes count for density and chords.
+#[derive(Debug, Clone)]
+pub struct ChartStats {
+    pub totals: NoteTotals,
+
+    /// Notes per second of each window, at its start.
+    pub density: Timeline<Seconds, Ratio<usize>>,
+    pub peak: Option<PeakWindow>,
+
+    /// The number of chords by their size; `chords[n]` for `n` notes at once.
+    pub chords: Vec<usize>,
+}
+
+impl ChartStats {
+    /// Computes statistics with specified window length. Panics if the window is zero.
+    pub fn new(chart: &Chart, clock: &ChartClock, window: Seconds) -> ChartStats {
+        assert!(window > Seconds::zero(), "invalid window");
+
+        let mut totals = NoteTotals::default();
+        for (_, note) in chart.notes() {
+            let count = match note.kind {
+                NoteKind::Tap => &mut totals.tap,
+                NoteKind::Hold { .. } => &mut totals.hold,
+                NoteKind::Mine => &mut totals.mine,
+                NoteKind::Invisible => &mut totals.invisible,
+                NoteKind::Fake => &mut totals.fake,
+            };
+            *count += 1;
+            if note.is_judged() && chart.lane_kind(note.lane) == Some(LaneKind::Scratch) {
+                totals.scratch += 1;
+            }
+        }
+
+        let mut times: Vec<Instant> = chart
+            .notes()
+            .filter(|(_, note)| note.is_judged())
+            .map(|(time, _)| time)
+            .collect();
+        times.sort();
+        let seconds: Vec<_> = times.iter().map(|&t| clock.seconds_at(t)).collect();
+
+        ChartStats {
+            totals,
+            density: density(&seconds, window.0),
+            peak: peak(&seconds, window.0),
+            chords: chords(&times),
+        }
+    }
+}
+
+/// Buckets sorted seconds into windows from zero.
+fn density(seconds: &[Ratio<usize>], window: Ratio<usize>) -> Timeline<Seconds, Ratio<usize>> {
+    let mut timeline = Timeline::new();
+    let Some(last) = seconds.last() else {
+        return timeline;
+    };
+
+    let mut rest = seconds;
+    for k in 0..=(last / window).to_integer() {
+        let end = window * (k + 1);
+        let count = rest.partition_point(|s| *s < end);
+        timeline.append(Seconds(window * k), Ratio::from_integer(count) / window);
+        rest = &rest[count..];
+    }
+    timeline
+}
+
+/// Finds the window starting at a note which contains the most notes; the earliest on tie.
+fn peak(seconds: &[Ratio<usize>], window: Ratio<usize>) -> Option<PeakWindow> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut end = 0;
+    for (start, &s) in seconds.iter().enumerate() {
+        while end < seconds.len() && seconds[end] < s + window {
+            end += 1;
+        }
+        if best.is_none_or(|(_, notes)| end - start > notes) {
+            best = Some((start, end - start));
+        }
+    }
+
+    best.map(|(start, notes)| PeakWindow {
+        start: Seconds(seconds[start]),
+        notes,
+        nps: Ratio::from_integer(notes) / window,
+    })
+}
+
+/// Counts chords of sorted times by size.
+fn chords(times: &[Instant]) -> Vec<usize> {
+    let mut chords = vec![0];
+    for group in times.chunk_by(|a, b| a == b) {
+        if chords.len() <= group.len() {
+            chords.resize(group.len() + 1, 0);
+        }
+        chords[group.len()] += 1;
+    }
+    chords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChartStats, NoteTotals};
+    use crate::{
+        fixture::{self, hold},
+        instant,
+        note::{LaneKind, NoteKind},
+        time::Seconds,
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn chart_stats_work() {
+        // a measure is 2 seconds
+        let kinds = vec![
+            LaneKind::Key,
+            LaneKind::Scratch,
+            LaneKind::Key,
+            LaneKind::Key,
+        ];
+        let (chart, clock) = fixture::chart_with(
+            fixture::rhythm(120),
+            kinds,
+            &[
+                (instant![0:1/4], 0, NoteKind::Tap),
+                (instant![0:1/4], 1, NoteKind::Tap),
+                (instant![1:0/1], 0, NoteKind::Tap),
+                (instant![1:1/8], 1, NoteKind::Tap),
+                (instant![1:1/4], 2, hold(instant![2:0/1])),
+                (instant![1:1/4], 0, NoteKind::Tap),
+                (instant![1:1/4], 3, NoteKind::Tap),
+                (instant![1:1/2], 1, NoteKind::Mine),
+            ],
+        );
+
+        let stats = ChartStats::new(&chart, &clock, Seconds(Ratio::from_integer(1)));
+        assert_eq!(
+            stats.totals,
+            NoteTotals {
+                tap: 6,
+                hold: 1,
+                scratch: 2,
+                mine: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.totals.judged(), 7);
+
+        let density: Vec<_> = stats.density.pairs().map(|(t, nps)| (t.0, *nps)).collect();
+        assert_eq!(
+            density,
+            vec![
+                (Ratio::from_integer(0), Ratio::from_integer(2)),
+                (Ratio::from_integer(1), Ratio::from_integer(0)),
+                (Ratio::from_integer(2), Ratio::from_integer(5)),
+            ]
+        );
+
+        let peak = stats.peak.unwrap();
+        assert_eq!(peak.start, Seconds(Ratio::from_integer(2)));
+        assert_eq!(peak.notes, 5);
+        assert_eq!(stats.chords, vec![0, 2, 1, 1]);
+    }
+
+    #[test]
+    fn chart_stats_of_edge_charts_work() {
+        let window = Seconds(Ratio::from_integer(1));
+        let (empty, clock) = fixture::chart(4, &[]);
+        let stats = ChartStats::new(&empty, &clock, window);
+        assert_eq!(stats.totals, NoteTotals::default());
+        assert!(stats.density.is_empty());
+        assert_eq!(stats.peak, None);
+        assert_eq!(stats.chords, vec![0]);
+
+        // the single note is exactly at a window boundary
+        let (single, clock) = fixture::chart(4, &[(instant![1:0/1], 2, NoteKind::Tap)]);
+        let stats = ChartStats::new(&single, &clock, window);
+        let density: Vec<_> = stats.density.pairs().map(|(t, nps)| (t.0, *nps)).collect();
+        assert_eq!(
+            density.last(),
+            Some(&(Ratio::from_integer(2), Ratio::from_integer(1)))
+        );
+        assert_eq!(density.len(), 3);
+        assert_eq!(stats.peak.unwrap().start, Seconds(Ratio::from_integer(2)));
+        assert_eq!(stats.chords, vec![0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid window")]
+    fn zero_window_panics() {
+        let (chart, clock) = fixture::chart(4, &[]);
+        ChartStats::new(&chart, &clock, Seconds::zero());
+    }
+}
@@ -0,0 +1,182 @@
+//! Contains autoplay input generation.
+
+use crate::{
+    chart::Chart,
+    clock::ChartClock,
+    judge::{InputAction, InputEvent},
+    time::SignedSeconds,
+    util::{signed_ratio, SplitMix64},
+};
+
+use num::rational::Ratio;
+
+/// Resolution of uniform samples of humanization.
+const SAMPLE_RESOLUTION: usize = 1 << 20;
+
+/// Specifies seeded timing errors of autoplay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Humanize {
+    /// Standard deviation of timing errors, in seconds.
+    pub deviation: Ratio<usize>,
+    pub seed: u64,
+}
+
+impl Humanize {
+    /// Samples approximately normal error, bounded by six deviations (Irwin-Hall of 12 samples).
+    fn sample(&self, rng: &mut SplitMix64) -> Ratio<isize> {
+        let sum: usize = (0..12).map(|_| rng.below(SAMPLE_RESOLUTION)).sum();
+        let centered = Ratio::new(sum as isize, SAMPLE_RESOLUTION as isize) - 6;
+        centered * signed_ratio(self.deviation)
+    }
+}
+
+/// Generates input events hitting every judged note, in order of seconds.
+/// Taps are released at once, and holds at their end.
+pub fn autoplay(chart: &Chart, clock: &ChartClock, humanize: Option<Humanize>) -> Vec<InputEvent> {
+    let mut rng = humanize.map(|h| (h, SplitMix64::new(h.seed)));
+    let mut error = || match rng.as_mut() {
+        Some((humanize, rng)) => humanize.sample(rng),
+        None => Ratio::from_integer(0),
+    };
+
+    let mut events = vec![];
+    for (_, notes) in chart.lanes() {
+        let mut last_press: Option<Ratio<isize>> = None;
+        let mut last_release: Option<usize> = None;
+        for (time, note) in notes.pairs().filter(|(_, note)| note.is_judged()) {
+            // humanized presses keep their order in the lane
+            let mut press = clock.judgment_seconds_at(time) + error();
+            if let Some(last_press) = last_press {
+                press = press.max(last_press);
+            }
+            // humanized release must precede the next press, and follows its own press
+            if let Some(index) = last_release {
+                let release: &mut InputEvent = &mut events[index];
+                release.seconds = release.seconds.min(SignedSeconds(press));
+            }
+            let release = match note.hold_end() {
+                Some(end) => (clock.judgment_seconds_at(end) + error()).max(press),
+                None => press,
+            };
+            events.push(InputEvent {
+                lane: note.lane,
+                action: InputAction::Press,
+                seconds: SignedSeconds(press),
+            });
+            last_press = Some(press);
+            last_release = Some(events.len());
+            events.push(InputEvent {
+                lane: note.lane,
+                action: InputAction::Release,
+                seconds: SignedSeconds(release),
+            });
+        }
+    }
+
+    // stable; keeps the order of events at the same seconds in a lane
+    events.sort_by_key(|event| event.seconds);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{autoplay, Humanize};
+    use crate::{
+        fixture::{self, hold},
+        instant,
+        judge::{Grade, HitWindows, HoldMode, HoldRules, InputAction, Judge, JudgmentKind},
+        note::{LaneId, LaneKind, NoteKind},
+        time::Instant,
+    };
+
+    use num::{rational::Ratio, Signed};
+
+    #[test]
+    fn autoplay_works() {
+        let (chart, clock) = fixture::chart_with(
+            fixture::rhythm(150),
+            vec![LaneKind::Key; 2],
+            &[
+                (instant![1:0/1], 0, NoteKind::Tap),
+                (instant![1:1/8], 0, NoteKind::Tap),
+                (instant![1:1/8], 1, hold(instant![1:3/4])),
+                (instant![1:3/4], 1, NoteKind::Tap),
+                (instant![2:0/1], 0, NoteKind::Mine),
+            ],
+        );
+        let judge = || {
+            let holds = HoldRules {
+                mode: HoldMode::Cn,
+                release: HitWindows::iidx(),
+            };
+            Judge::with_holds(&chart, &clock, HitWindows::iidx(), holds)
+        };
+
+        let events = autoplay(&chart, &clock, None);
+        assert_eq!(events.len(), 8);
+        let judgments = judge().run(events);
+        assert_eq!(judgments.len(), 5);
+        assert!(judgments.iter().all(|j| matches!(
+            j.kind,
+            JudgmentKind::Hit(Grade::PGreat) | JudgmentKind::Release(Grade::PGreat)
+        )));
+
+        let humanize = Humanize {
+            deviation: Ratio::new(1, 100),
+            seed: 7,
+        };
+        let events = autoplay(&chart, &clock, Some(humanize));
+        assert_eq!(events, autoplay(&chart, &clock, Some(humanize)), "seeded");
+        assert_ne!(
+            events,
+            autoplay(
+                &chart,
+                &clock,
+                Some(Humanize {
+                    seed: 8,
+                    ..humanize
+                })
+            )
+        );
+        let judgments = judge().run(events);
+        assert_eq!(judgments.len(), 5);
+        assert!(judgments
+            .iter()
+            .all(|j| j.error.is_some_and(|e| e.abs() <= Ratio::new(6, 100))));
+    }
+
+    #[test]
+    fn humanized_events_keep_order_in_lane() {
+        // notes are 1/8 seconds apart, far closer than the deviation
+        let notes: Vec<_> = (0..16)
+            .map(|i| {
+                let kind = if i % 3 == 0 {
+                    hold(Instant::new_parts(1 + (i + 1) / 16, (i + 1) % 16, 16))
+                } else {
+                    NoteKind::Tap
+                };
+                (Instant::new_parts(1, i, 16), i % 2, kind)
+            })
+            .collect();
+        let (chart, clock) =
+            fixture::chart_with(fixture::rhythm(240), vec![LaneKind::Key; 2], &notes);
+
+        for seed in 0..64 {
+            let humanize = Humanize {
+                deviation: Ratio::new(1, 2),
+                seed,
+            };
+            let events = autoplay(&chart, &clock, Some(humanize));
+            assert_eq!(events.len(), 32);
+            for lane in [LaneId(0), LaneId(1)] {
+                let actions: Vec<_> = events.iter().filter(|e| e.lane == lane).collect();
+                assert!(actions.windows(2).all(|w| w[0].seconds <= w[1].seconds));
+                assert!(actions
+                    .chunks(2)
+                    .all(|pair| pair[0].action == InputAction::Press
+                        && pair[1].action == InputAction::Release
+                        && pair[0].seconds <= pair[1].seconds));
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Contains BPM statistics and soflan analysis over rhythm map.
+
+use crate::{
+    clock::ChartClock,
+    time::Instant,
+    timeline::Timeline,
+    value::{RhythmChange, Tempo},
+};
+
+use num::{rational::Ratio, One, Zero};
+
+/// Represents a section of constant tempo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TempoSegment {
+    pub start: Instant,
+    pub end: Instant,
+    pub tempo: Tempo,
+
+    /// Wall-clock duration in seconds.
+    pub seconds: Ratio<usize>,
+
+    /// Seconds of stops in the section, included in `seconds`.
+    pub stop_seconds: Ratio<usize>,
+}
+
+impl TempoSegment {
+    /// Returns seconds in which the chart moves at the tempo, excluding stops.
+    pub fn moving_seconds(&self) -> Ratio<usize> {
+        self.seconds - self.stop_seconds
+    }
+}
+
+/// Represents how much tempo changes (soflan) affect reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SoflanSeverity {
+    /// Constant tempo.
+    None,
+
+    /// Brief or small tempo changes.
+    Mild,
+
+    /// Noticeable tempo changes.
+    Moderate,
+
+    /// Long or large tempo changes.
+    Severe,
+}
+
+/// Represents BPM statistics of a chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BpmStats {
+    pub start: Tempo,
+    pub min: Tempo,
+    pub max: Tempo,
+
+    /// BPM which lasts longest in wall-clock time excluding stops. The earliest one wins a tie.
+    pub main: Tempo,
+
+    /// Constant tempo sections before the end.
+    pub segments: Vec<TempoSegment>,
+}
+
+impl BpmStats {
+    /// Computes statistics of rhythm map before `end`.
+    pub fn from_rhythm(rhythm: &Timeline<Instant, RhythmChange>, end: Instant) -> BpmStats {
+        BpmStats::new(&ChartClock::new(rhythm.clone()), end)
+    }
+
+    /// Computes statistics of clock before `end`.
+    /// The first section is always counted even if `end` is zero.
+    pub fn new(clock: &ChartClock, end: Instant) -> BpmStats {
+        let mut segments: Vec<TempoSegment> = vec![];
+        let mut sections = clock
+            .rhythm()
+            .sections()
+            .take_while(|(time, _, _)| *time == Instant::zero() || *time < end)
+            .map(|(time, RhythmChange(_, tempo), _)| (time, *tempo))
+            .peekable();
+        while let Some((start, tempo)) = sections.next() {
+            let until = sections.peek().map_or(end, |(next, _)| *next).max(start);
+            let seconds = clock.seconds_at(until) - clock.seconds_at(start);
+            let moving = clock.rhythm().fetch(until) - clock.rhythm().fetch(start);
+            let stop_seconds = seconds - moving;
+            match segments.last_mut() {
+                Some(last) if last.tempo == tempo => {
+                    last.end = until;
+                    last.seconds += seconds;
+                    last.stop_seconds += stop_seconds;
+                }
+                _ => segments.push(TempoSegment {
+                    start,
+                    end: until,
+                    tempo,
+                    seconds,
+                    stop_seconds,
+                }),
+            }
+        }
+
+        let start = segments.first().expect("must have rhythm").tempo;
+        let min = segments.iter().map(|s| s.tempo.0).min().map(Tempo);
+        let max = segments.iter().map(|s| s.tempo.0).max().map(Tempo);
+
+        let mut durations: Vec<(Tempo, Ratio<usize>)> = vec![];
+        for segment in &segments {
+            match durations.iter_mut().find(|(t, _)| *t == segment.tempo) {
+                Some((_, d)) => *d += segment.moving_seconds(),
+                None => durations.push((segment.tempo, segment.moving_seconds())),
+            }
+        }
+        let main = durations
+            .into_iter()
+            .reduce(|l, r| if r.1 > l.1 { r } else { l })
+            .map(|(t, _)| t);
+
+        BpmStats {
+            start,
+            min: min.expect("must have rhythm"),
+            max: max.expect("must have rhythm"),
+            main: main.expect("must have rhythm"),
+            segments,
+        }
+    }
+
+    /// Returns whether tempo changes at all.
+    pub fn has_soflan(&self) -> bool {
+        self.min != self.max
+    }
+
+    /// Returns the mean ratio between each tempo and main BPM (the larger divided by the smaller),
+    /// weighted by wall-clock duration excluding stops. Constant tempo gives 1.
+    pub fn deviation(&self) -> Ratio<usize> {
+        let total: Ratio<usize> = self.segments.iter().map(|s| s.moving_seconds()).sum();
+        if total.is_zero() {
+            return Ratio::one();
+        }
+
+        let main = self.main.0;
+        let weighted: Ratio<usize> = self
+            .segments
+            .iter()
+            .map(|s| s.moving_seconds() * (s.tempo.0 / main).max(main / s.tempo.0))
+            .sum();
+        weighted / total
+    }
+
+    /// Classifies soflan severity by `deviation`.
+    pub fn severity(&self) -> SoflanSeverity {
+        if !self.has_soflan() {
+            return SoflanSeverity::None;
+        }
+
+        let deviation = self.deviation();
+        if deviation < Ratio::new(11, 10) {
+            SoflanSeverity::Mild
+        } else if deviation < Ratio::new(3, 2) {
+            SoflanSeverity::Moderate
+        } else {
+            SoflanSeverity::Severe
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BpmStats, SoflanSeverity};
+    use crate::{
+        clock::ChartClock,
+        instant, timeline,
+        value::{Beat, RhythmChange, Stop, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    fn rhythm_change(beat: usize, tempo: usize) -> RhythmChange {
+        RhythmChange(
+            Beat(Ratio::from_integer(beat)),
+            Tempo(Ratio::from_integer(tempo)),
+        )
+    }
+
+    #[test]
+    fn bpm_stats_work() {
+        let rhythm = timeline! {
+            [0:0/1]: rhythm_change(4, 150),
+            [1:0/1]: rhythm_change(4, 75),
+            [2:0/1]: rhythm_change(3, 75),
+            [3:0/1]: rhythm_change(4, 300),
+            [9:0/1]: rhythm_change(4, 150),
+        };
+        let stats = BpmStats::from_rhythm(&rhythm, instant![4:0/1]);
+
+        assert_eq!(stats.start, Tempo(Ratio::from_integer(150)));
+        assert_eq!(stats.min, Tempo(Ratio::from_integer(75)));
+        assert_eq!(stats.max, Tempo(Ratio::from_integer(300)));
+        assert_eq!(stats.main, Tempo(Ratio::from_integer(75)));
+
+        let segments: Vec<_> = stats
+            .segments
+            .iter()
+            .map(|s| (s.start, s.end, s.seconds))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (instant![0:0/1], instant![1:0/1], Ratio::new(8, 5)),
+                (instant![1:0/1], instant![3:0/1], Ratio::new(28, 5)),
+                (instant![3:0/1], instant![4:0/1], Ratio::new(4, 5)),
+            ],
+            "same tempo sections are joined"
+        );
+
+        // (8/5 * 2 + 28/5 * 1 + 4/5 * 4) / 8
+        assert_eq!(stats.deviation(), Ratio::new(3, 2));
+        assert_eq!(stats.severity(), SoflanSeverity::Severe);
+    }
+
+    #[test]
+    fn constant_tempo_has_no_soflan() {
+        let rhythm = timeline! {
+            [0:0/1]: rhythm_change(4, 150),
+            [1:0/1]: rhythm_change(7, 150),
+        };
+        let stats = BpmStats::from_rhythm(&rhythm, instant![8:0/1]);
+        assert_eq!(stats.segments.len(), 1);
+        assert_eq!(stats.deviation(), Ratio::from_integer(1));
+        assert_eq!(stats.severity(), SoflanSeverity::None);
+    }
+
+    #[test]
+    fn main_bpm_excludes_stops() {
+        // 120 BPM lasts 2 seconds and stops 4 seconds, 240 BPM lasts 3 seconds
+        let clock = ChartClock::new(timeline! {
+            [0:0/1]: rhythm_change(4, 120),
+            [1:0/1]: rhythm_change(4, 240),
+        })
+        .with_stops(timeline! { [0:1/2]: Stop(Ratio::new(8, 1)), });
+        let stats = BpmStats::new(&clock, instant![4:0/1]);
+
+        assert_eq!(stats.segments[0].seconds, Ratio::from_integer(6));
+        assert_eq!(stats.segments[0].stop_seconds, Ratio::from_integer(4));
+        assert_eq!(stats.main, Tempo(Ratio::from_integer(240)));
+        // (2 * 2 + 3 * 1) / 5
+        assert_eq!(stats.deviation(), Ratio::new(7, 5));
+    }
+}
@@ -0,0 +1,443 @@
+//! Contains chart aggregate.
+
+use crate::{
+    clock::ChartClock,
+    note::{KeysoundId, LaneId, LaneKind, Note, NoteKind},
+    span::SpanTimeline,
+    time::Instant,
+    timeline::Timeline,
+    util::StableHasher,
+    value::{Beat, RhythmChange, Tempo},
+};
+
+use std::hash::Hasher;
+
+use num::rational::Ratio;
+use thiserror::Error as ThisError;
+
+/// Represents an error about `Chart`.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum ChartError {
+    /// Note refers a lane which chart doesn't have.
+    #[error("lane {0:?} is out of range")]
+    LaneOutOfRange(LaneId),
+
+    /// Another note already exists at the same time in the lane.
+    #[error("lane {0:?} already has a note at {1:?}")]
+    DuplicateNote(LaneId, Instant),
+
+    /// Hold note ends before it starts.
+    #[error("hold note at {0:?} ends at {1:?}")]
+    InvalidHoldEnd(Instant, Instant),
+}
+
+/// Represents play length of a chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChartLength {
+    /// The last event time.
+    pub end: Instant,
+
+    /// Elapsed seconds when all events finish.
+    pub end_seconds: Ratio<usize>,
+}
+
+impl ChartLength {
+    /// Pads to the start of the measure following the end seconds.
+    pub fn padded_to_measure(&self, clock: &ChartClock) -> ChartLength {
+        let end = clock
+            .instant_at(self.end_seconds)
+            .ceil_measure()
+            .max(self.end);
+        ChartLength {
+            end,
+            end_seconds: clock.seconds_after(end).max(self.end_seconds),
+        }
+    }
+}
+
+/// Represents a chart; notes tied to its rhythm map.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    rhythm: Timeline<Instant, RhythmChange>,
+    lane_kinds: Vec<LaneKind>,
+    lanes: Vec<Timeline<Instant, Note>>,
+    holds: Vec<SpanTimeline<Instant, Note>>,
+    bgm: Timeline<Instant, KeysoundId>,
+}
+
+impl Chart {
+    /// Creates new chart with empty key lanes.
+    pub fn new(rhythm: Timeline<Instant, RhythmChange>, lane_count: usize) -> Chart {
+        Chart::with_lanes(rhythm, vec![LaneKind::Key; lane_count])
+    }
+
+    /// Creates new chart with empty lanes of specified kinds.
+    pub fn with_lanes(rhythm: Timeline<Instant, RhythmChange>, lane_kinds: Vec<LaneKind>) -> Chart {
+        let lane_count = lane_kinds.len();
+        Chart {
+            rhythm,
+            lane_kinds,
+            lanes: (0..lane_count).map(|_| Timeline::new()).collect(),
+            holds: (0..lane_count).map(|_| SpanTimeline::new()).collect(),
+            bgm: Timeline::new(),
+        }
+    }
+
+    /// Returns rhythm map.
+    pub fn rhythm(&self) -> &Timeline<Instant, RhythmChange> {
+        &self.rhythm
+    }
+
+    /// Returns the number of lanes.
+    pub fn lane_count(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Returns kinds of lanes.
+    pub fn lane_kinds(&self) -> &[LaneKind] {
+        &self.lane_kinds
+    }
+
+    /// Returns kind of specified lane.
+    pub fn lane_kind(&self, lane: LaneId) -> Option<LaneKind> {
+        self.lane_kinds.get(lane.0).copied()
+    }
+
+    /// Returns note timeline of specified lane.
+    pub fn lane(&self, lane: LaneId) -> Option<&Timeline<Instant, Note>> {
+        self.lanes.get(lane.0)
+    }
+
+    /// Returns hold note spans of specified lane.
+    pub fn holds(&self, lane: LaneId) -> Option<&SpanTimeline<Instant, Note>> {
+        self.holds.get(lane.0)
+    }
+
+    /// Returns iterator of lanes.
+    pub fn lanes(&self) -> impl Iterator<Item = (LaneId, &Timeline<Instant, Note>)> {
+        self.lanes.iter().enumerate().map(|(i, tl)| (LaneId(i), tl))
+    }
+
+    /// Returns iterator of all notes, lane by lane.
+    pub fn notes(&self) -> impl Iterator<Item = (Instant, &Note)> {
+        self.lanes.iter().flat_map(|tl| tl.pairs())
+    }
+
+    /// Returns the number of all notes.
+    pub fn note_count(&self) -> usize {
+        self.lanes.iter().map(|tl| tl.len()).sum()
+    }
+
+    /// Returns the time of the last note, or end of the last hold note.
+    pub fn last_note_time(&self) -> Option<Instant> {
+        self.notes()
+            .map(|(time, note)| note.hold_end().unwrap_or(time))
+            .max()
+    }
+
+    /// Returns background keysound timeline (BMS channel `01`).
+    pub fn bgm(&self) -> &Timeline<Instant, KeysoundId> {
+        &self.bgm
+    }
+
+    /// Inserts a background keysound.
+    pub fn insert_bgm(&mut self, time: Instant, keysound: KeysoundId) {
+        self.bgm.insert(time, keysound);
+    }
+
+    /// Returns hash of lane kinds, rhythm map, notes and background keysounds, stable across platforms.
+    /// Each field is encoded explicitly, and each sequence is prefixed by its length.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write_usize(self.lane_kinds.len());
+        for kind in &self.lane_kinds {
+            hasher.write_u8(match kind {
+                LaneKind::Key => 0,
+                LaneKind::Scratch => 1,
+            });
+        }
+
+        hasher.write_usize(self.rhythm.len());
+        for (time, RhythmChange(Beat(beat), Tempo(tempo))) in self.rhythm.pairs() {
+            write_instant(&mut hasher, time);
+            write_ratio(&mut hasher, *beat);
+            write_ratio(&mut hasher, *tempo);
+        }
+
+        hasher.write_usize(self.note_count());
+        for (time, note) in self.notes() {
+            write_instant(&mut hasher, time);
+            hasher.write_usize(note.lane.0);
+            match note.kind {
+                NoteKind::Tap => hasher.write_u8(0),
+                NoteKind::Hold { end } => {
+                    hasher.write_u8(1);
+                    write_instant(&mut hasher, end);
+                }
+                NoteKind::Mine => hasher.write_u8(2),
+                NoteKind::Invisible => hasher.write_u8(3),
+                NoteKind::Fake => hasher.write_u8(4),
+            }
+            write_keysound(&mut hasher, note.keysound);
+        }
+
+        hasher.write_usize(self.bgm.len());
+        for (time, keysound) in self.bgm.pairs() {
+            write_instant(&mut hasher, time);
+            write_keysound(&mut hasher, Some(*keysound));
+        }
+        hasher.finish()
+    }
+
+    /// Computes play length.
+    /// The end is the latest of notes, hold ends, background keysounds and stops,
+    /// and keysounds are extended by `sound_length` (in seconds) if it is known.
+    pub fn length(
+        &self,
+        clock: &ChartClock,
+        sound_length: impl Fn(KeysoundId) -> Option<Ratio<usize>>,
+    ) -> ChartLength {
+        let end = [
+            self.last_note_time(),
+            self.bgm.times().last(),
+            clock.last_stop_time(),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_else(Instant::zero);
+
+        let sound_tails = self
+            .notes()
+            .filter_map(|(time, note)| Some((time, note.keysound?)))
+            .chain(self.bgm.pairs().map(|(time, keysound)| (time, *keysound)))
+            .filter_map(|(time, keysound)| Some(clock.seconds_at(time) + sound_length(keysound)?));
+        let end_seconds = sound_tails.fold(clock.seconds_after(end), |l, r| l.max(r));
+
+        ChartLength { end, end_seconds }
+    }
+
+    /// Inserts a note into its lane.
+    /// Reindexes hold notes of the lane, so bulk insertion should use `insert_notes`.
+    pub fn insert_note(&mut self, time: Instant, note: Note) -> Result<(), ChartError> {
+        self.insert_into_lane(time, note)?;
+        if let Some(end) = note.hold_end() {
+            self.holds[note.lane.0].insert(time, end, note);
+        }
+        Ok(())
+    }
+
+    /// Inserts notes into their lanes, indexing hold notes once at last.
+    /// Stops at the first invalid note, keeping the notes before it.
+    pub fn insert_notes(
+        &mut self,
+        notes: impl IntoIterator<Item = (Instant, Note)>,
+    ) -> Result<(), ChartError> {
+        let mut holds = vec![vec![]; self.lane_count()];
+        let result = notes.into_iter().try_for_each(|(time, note)| {
+            self.insert_into_lane(time, note)?;
+            if let Some(end) = note.hold_end() {
+                holds[note.lane.0].push((time, end, note));
+            }
+            Ok(())
+        });
+        for (spans, inserted) in self.holds.iter_mut().zip(holds) {
+            if !inserted.is_empty() {
+                spans.extend(inserted);
+            }
+        }
+        result
+    }
+
+    /// Inserts a note into its lane timeline, leaving hold spans untouched.
+    fn insert_into_lane(&mut self, time: Instant, note: Note) -> Result<(), ChartError> {
+        if let NoteKind::Hold { end } = note.kind {
+            if end <= time {
+                return Err(ChartError::InvalidHoldEnd(time, end));
+            }
+        }
+
+        let lane = self
+            .lanes
+            .get_mut(note.lane.0)
+            .ok_or(ChartError::LaneOutOfRange(note.lane))?;
+        if lane.get(time).is_some() {
+            return Err(ChartError::DuplicateNote(note.lane, time));
+        }
+        lane.insert(time, note);
+        Ok(())
+    }
+}
+
+fn write_instant(hasher: &mut StableHasher, time: Instant) {
+    hasher.write_usize(time.measure());
+    write_ratio(hasher, time.submeasure());
+}
+
+/// Writes in lowest terms, so that equal rationals are hashed equally.
+fn write_ratio(hasher: &mut StableHasher, value: Ratio<usize>) {
+    let value = value.reduced();
+    hasher.write_usize(*value.numer());
+    hasher.write_usize(*value.denom());
+}
+
+fn write_keysound(hasher: &mut StableHasher, keysound: Option<KeysoundId>) {
+    match keysound {
+        Some(KeysoundId(id)) => {
+            hasher.write_u8(1);
+            hasher.write_usize(id);
+        }
+        None => hasher.write_u8(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chart, ChartError, ChartLength};
+    use crate::{
+        clock::ChartClock,
+        instant,
+        note::{KeysoundId, LaneId, LaneKind, Note, NoteKind},
+        timeline,
+        value::{Beat, RhythmChange, Stop, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn chart_insertion_works() {
+        let rhythm = timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        };
+        let mut chart = Chart::new(rhythm, 2);
+
+        let tap = Note::new(LaneId(0), NoteKind::Tap);
+        assert_eq!(chart.insert_note(instant![1:1/4], tap), Ok(()));
+        assert_eq!(chart.insert_note(instant![0:1/2], tap), Ok(()));
+        assert_eq!(
+            chart.insert_note(instant![1:1/4], tap),
+            Err(ChartError::DuplicateNote(LaneId(0), instant![1:1/4]))
+        );
+        assert_eq!(
+            chart.insert_note(instant![0:0/1], Note::new(LaneId(2), NoteKind::Tap)),
+            Err(ChartError::LaneOutOfRange(LaneId(2)))
+        );
+
+        let hold = Note::new(
+            LaneId(1),
+            NoteKind::Hold {
+                end: instant![0:1/2],
+            },
+        );
+        assert_eq!(
+            chart.insert_note(instant![0:1/2], hold),
+            Err(ChartError::InvalidHoldEnd(instant![0:1/2], instant![0:1/2]))
+        );
+        assert_eq!(chart.insert_note(instant![0:1/4], hold), Ok(()));
+
+        assert_eq!(chart.note_count(), 3);
+        assert_eq!(chart.last_note_time(), Some(instant![1:1/4]));
+        assert_eq!(
+            chart.holds(LaneId(1)).unwrap().len(),
+            1,
+            "hold span is indexed"
+        );
+        chart.insert_bgm(instant![1:0/1], KeysoundId(0));
+        let clock = ChartClock::new(chart.rhythm().clone())
+            .with_stops(timeline! { [1:1/4]: Stop(Ratio::new(1, 1)), });
+        assert_eq!(
+            chart.length(&clock, |_| None),
+            ChartLength {
+                end: instant![1:1/4],
+                end_seconds: Ratio::new(3, 1),
+            },
+            "trailing stop is counted"
+        );
+        let length = chart.length(&clock, |_| Some(Ratio::new(5, 2)));
+        assert_eq!(length.end_seconds, Ratio::new(9, 2), "bgm tail is counted");
+        assert_eq!(
+            length.padded_to_measure(&clock),
+            ChartLength {
+                end: instant![2:0/1],
+                end_seconds: Ratio::new(9, 2),
+            },
+            "padded to next measure"
+        );
+
+        let lane0: Vec<_> = chart.lane(LaneId(0)).unwrap().times().collect();
+        assert_eq!(
+            lane0,
+            vec![instant![0:1/2], instant![1:1/4]],
+            "lane is sorted"
+        );
+    }
+
+    #[test]
+    fn bulk_insertion_works() {
+        let rhythm = timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        };
+        let mut chart = Chart::new(rhythm, 2);
+        let hold = |lane, end| Note::new(LaneId(lane), NoteKind::Hold { end });
+
+        assert_eq!(
+            chart.insert_notes([
+                (instant![1:0/1], hold(0, instant![2:0/1])),
+                (instant![0:0/1], hold(0, instant![0:1/2])),
+                (instant![0:1/4], Note::new(LaneId(1), NoteKind::Tap)),
+            ]),
+            Ok(())
+        );
+        assert_eq!(chart.note_count(), 3);
+        let active: Vec<_> = chart
+            .holds(LaneId(0))
+            .unwrap()
+            .active_at(instant![1:1/2])
+            .into_iter()
+            .map(|(start, end, _)| (start, end))
+            .collect();
+        assert_eq!(active, vec![(instant![1:0/1], instant![2:0/1])]);
+
+        assert_eq!(
+            chart.insert_notes([
+                (instant![2:0/1], hold(1, instant![3:0/1])),
+                (instant![1:0/1], hold(0, instant![1:1/2])),
+            ]),
+            Err(ChartError::DuplicateNote(LaneId(0), instant![1:0/1]))
+        );
+        assert_eq!(
+            chart.holds(LaneId(1)).unwrap().len(),
+            1,
+            "notes before the invalid one are kept and indexed"
+        );
+    }
+
+    #[test]
+    fn scratch_lane_works() {
+        let rhythm = timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        };
+        let mut chart = Chart::with_lanes(rhythm, vec![LaneKind::Scratch, LaneKind::Key]);
+        let hold = Note::new(
+            LaneId(0),
+            NoteKind::Hold {
+                end: instant![1:0/1],
+            },
+        );
+        assert_eq!(
+            chart.insert_note(instant![0:1/2], hold),
+            Ok(()),
+            "scratch hold"
+        );
+        assert_eq!(chart.lane_kind(LaneId(0)), Some(LaneKind::Scratch));
+        assert_eq!(chart.lane_kind(LaneId(2)), None);
+        assert_ne!(
+            chart.stable_hash(),
+            Chart::new(chart.rhythm().clone(), 2).stable_hash(),
+        );
+        assert_eq!(
+            Chart::new(chart.rhythm().clone(), 2).lane_kinds(),
+            &[LaneKind::Key; 2]
+        );
+    }
+}
@@ -0,0 +1,658 @@
+//! Contains chart clock, mapping between `Instant` and wall-clock seconds.
+
+use crate::{
+    preintegral::{Preintegral, PreintegralError},
+    time::Instant,
+    timeline::Timeline,
+    util::{
+        checked_cast_ratio, checked_signed_ratio, lower_bound, signed_ratio, unsigned_ratio,
+        upper_bound,
+    },
+    value::{RhythmChange, Stop, Tempo},
+};
+
+use num::{
+    rational::Ratio, CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromPrimitive, Integer,
+    ToPrimitive, Zero,
+};
+
+/// Represents offsets between playback and chart, in seconds.
+/// Positive offsets delay the chart.
+/// The chart offset is in song seconds, and user offsets are in playback (real) seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Offsets {
+    /// Song seconds at which the chart starts; the negation of StepMania `#OFFSET`.
+    pub chart: Ratio<isize>,
+
+    /// User offset for judgment, compensating audio latency.
+    pub audio: Ratio<isize>,
+
+    /// User offset for rendering, compensating display latency.
+    pub visual: Ratio<isize>,
+}
+
+/// Represents a section of constant playback rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RateSegment {
+    playback: Ratio<isize>,
+    song: Ratio<isize>,
+    rate: Ratio<isize>,
+}
+
+impl RateSegment {
+    const NORMAL: RateSegment = RateSegment {
+        playback: Ratio::new_raw(0, 1),
+        song: Ratio::new_raw(0, 1),
+        rate: Ratio::new_raw(1, 1),
+    };
+}
+
+/// Maps chart time to elapsed seconds and vice versa.
+///
+/// Elapsed seconds are `Ratio<N>`; `usize` is used by default, and `num::BigInt` never overflows.
+/// All arithmetic on seconds is checked. The `try_` methods return
+/// `PreintegralError::Overflow` on overflow, and the others panic instead of wrapping.
+/// Playback seconds, offsets and playback rates are `Ratio<isize>` on any clock.
+#[derive(Debug, Clone)]
+pub struct ChartClock<N = usize>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    rhythm: Preintegral<Instant, RhythmChange<N>>,
+    offsets: Offsets,
+    rates: Vec<RateSegment>,
+    stop_times: Vec<Instant>,
+
+    /// Elapsed seconds at which each stop starts.
+    stop_starts: Vec<Ratio<N>>,
+
+    /// Total seconds of stops before each stop, and of all stops at last.
+    stop_offsets: Vec<Ratio<N>>,
+}
+
+impl<N> ChartClock<N>
+where
+    N: Clone + Integer + FromPrimitive + ToPrimitive + CheckedAdd + CheckedSub + CheckedMul,
+{
+    /// Creates new clock. Rhythm map must be zero-aligned and every tempo must be positive.
+    /// Panics if elapsed seconds overflow.
+    pub fn new(rhythm: Timeline<Instant, RhythmChange<N>>) -> ChartClock<N> {
+        ChartClock::try_new(rhythm).expect("invalid rhythm map")
+    }
+
+    /// Creates new clock, failing instead of panicking when elapsed seconds overflow.
+    pub fn try_new(
+        rhythm: Timeline<Instant, RhythmChange<N>>,
+    ) -> Result<ChartClock<N>, PreintegralError> {
+        Ok(ChartClock {
+            rhythm: Preintegral::try_new(rhythm)?,
+            offsets: Offsets::default(),
+            rates: vec![RateSegment::NORMAL],
+            stop_times: vec![],
+            stop_starts: vec![],
+            stop_offsets: vec![Ratio::zero()],
+        })
+    }
+
+    /// Sets stops. Stop durations are resolved by tempo at each stop.
+    /// Panics if elapsed seconds overflow.
+    pub fn with_stops(self, stops: Timeline<Instant, Stop>) -> ChartClock<N> {
+        self.try_with_stops(stops)
+            .expect("elapsed seconds overflow")
+    }
+
+    /// Sets stops, failing instead of panicking when elapsed seconds overflow.
+    pub fn try_with_stops(
+        self,
+        stops: Timeline<Instant, Stop>,
+    ) -> Result<ChartClock<N>, PreintegralError> {
+        let overflow = || PreintegralError::Overflow;
+        let sixty = Ratio::from_integer(N::from_usize(60).ok_or_else(overflow)?);
+        let mut stop_times = vec![];
+        let mut stop_starts = vec![];
+        let mut stop_offsets = vec![Ratio::zero()];
+        for (time, Stop(beats)) in stops.into_pairs() {
+            let offset = stop_offsets.last().expect("must have item").clone();
+            let duration = checked_cast_ratio::<N>(beats)
+                .ok_or_else(overflow)?
+                .checked_mul(&sixty)
+                .and_then(|b| b.checked_div(&self.tempo_at(time).0))
+                .ok_or_else(overflow)?;
+            let start = self.rhythm.try_fetch(time)?;
+            stop_times.push(time);
+            stop_starts.push(start.checked_add(&offset).ok_or_else(overflow)?);
+            stop_offsets.push(offset.checked_add(&duration).ok_or_else(overflow)?);
+        }
+
+        Ok(ChartClock {
+            stop_times,
+            stop_starts,
+            stop_offsets,
+            ..self
+        })
+    }
+
+    /// Returns preintegrated rhythm map.
+    pub fn rhythm(&self) -> &Preintegral<Instant, RhythmChange<N>> {
+        &self.rhythm
+    }
+
+    /// Returns the time of the last stop.
+    pub fn last_stop_time(&self) -> Option<Instant> {
+        self.stop_times.last().copied()
+    }
+
+    /// Returns rhythm at specified time.
+    pub fn rhythm_at(&self, time: Instant) -> RhythmChange<N> {
+        self.rhythm.item_at(time).clone()
+    }
+
+    /// Returns tempo at specified time.
+    pub fn tempo_at(&self, time: Instant) -> Tempo<N> {
+        self.rhythm_at(time).1
+    }
+
+    /// Returns elapsed seconds at specified time, before the stop at the time.
+    /// Panics if elapsed seconds overflow.
+    pub fn seconds_at(&self, time: Instant) -> Ratio<N> {
+        self.try_seconds_at(time).expect("elapsed seconds overflow")
+    }
+
+    /// Returns elapsed seconds at specified time, before the stop at the time.
+    pub fn try_seconds_at(&self, time: Instant) -> Result<Ratio<N>, PreintegralError> {
+        self.stopped_seconds(time, lower_bound(&self.stop_times, &time))
+    }
+
+    /// Returns elapsed seconds at specified time, after the stop at the time.
+    /// Panics if elapsed seconds overflow.
+    pub fn seconds_after(&self, time: Instant) -> Ratio<N> {
+        self.try_seconds_after(time)
+            .expect("elapsed seconds overflow")
+    }
+
+    /// Returns elapsed seconds at specified time, after the stop at the time.
+    pub fn try_seconds_after(&self, time: Instant) -> Result<Ratio<N>, PreintegralError> {
+        self.stopped_seconds(time, upper_bound(&self.stop_times, &time))
+    }
+
+    /// Returns chart time at specified elapsed seconds.
+    /// Returns the time of stop while stopping. Panics if the time overflows.
+    pub fn instant_at(&self, seconds: Ratio<N>) -> Instant {
+        self.try_instant_at(seconds).expect("chart time overflow")
+    }
+
+    /// Returns chart time at specified elapsed seconds.
+    /// Returns the time of stop while stopping.
+    pub fn try_instant_at(&self, seconds: Ratio<N>) -> Result<Instant, PreintegralError> {
+        let overflow = || PreintegralError::Overflow;
+        let passed_stops = upper_bound(&self.stop_starts, &seconds);
+        if passed_stops > 0 {
+            let stop_end = self.stop_starts[passed_stops - 1]
+                .checked_add(&self.stop_duration(passed_stops - 1))
+                .ok_or_else(overflow)?;
+            if seconds < stop_end {
+                return Ok(self.stop_times[passed_stops - 1]);
+            }
+        }
+
+        let seconds = seconds
+            .checked_sub(&self.stop_offsets[passed_stops])
+            .ok_or_else(overflow)?;
+        let (start, RhythmChange(beat, tempo), base) = self.rhythm.section_by_value(&seconds);
+        let sixty = Ratio::from_integer(N::from_usize(60).ok_or_else(overflow)?);
+        let measures = seconds
+            .checked_sub(base)
+            .and_then(|s| s.checked_mul(&tempo.0))
+            .and_then(|s| s.checked_div(&beat.0.checked_mul(&sixty)?))
+            .and_then(|m| Some(Ratio::new(m.numer().to_usize()?, m.denom().to_usize()?)))
+            .and_then(|m| start.to_measures().checked_add(&m))
+            .ok_or_else(overflow)?;
+        Ok(Instant::from_measures(measures))
+    }
+
+    /// Sets offsets.
+    pub fn with_offsets(self, offsets: Offsets) -> ChartClock<N> {
+        ChartClock { offsets, ..self }
+    }
+
+    pub fn offsets(&self) -> Offsets {
+        self.offsets
+    }
+
+    /// Sets playback rate for the whole song. Panics if rate is zero or exceeds `isize`.
+    pub fn with_rate(self, rate: Ratio<usize>) -> ChartClock<N> {
+        assert!(rate > Ratio::zero(), "invalid rate");
+        let rates = vec![RateSegment {
+            rate: signed_ratio(rate),
+            ..RateSegment::NORMAL
+        }];
+        ChartClock { rates, ..self }
+    }
+
+    /// Changes playback rate from specified playback seconds, keeping song position continuous.
+    /// Later rate changes are discarded. Panics if rate is zero or seconds overflow.
+    pub fn change_rate(&mut self, playback: Ratio<isize>, rate: Ratio<usize>) {
+        self.try_change_rate(playback, rate)
+            .expect("playback seconds overflow");
+    }
+
+    /// Changes playback rate from specified playback seconds, keeping song position continuous.
+    /// Later rate changes are discarded. Panics if rate is zero.
+    pub fn try_change_rate(
+        &mut self,
+        playback: Ratio<isize>,
+        rate: Ratio<usize>,
+    ) -> Result<(), PreintegralError> {
+        assert!(rate > Ratio::zero(), "invalid rate");
+        let rate = checked_signed_ratio(rate).ok_or(PreintegralError::Overflow)?;
+        let song = self.try_song_seconds_at(playback)?;
+        let kept = self.rates.partition_point(|r| r.playback < playback);
+        self.rates.truncate(kept.max(1));
+        self.rates.push(RateSegment {
+            playback,
+            song,
+            rate,
+        });
+        Ok(())
+    }
+
+    /// Returns whether playback rate changes during the song.
+    pub fn has_rate_changes(&self) -> bool {
+        self.rates.len() > 1
+    }
+
+    /// Returns playback rate at specified playback seconds.
+    pub fn rate_at(&self, playback: Ratio<isize>) -> Ratio<usize> {
+        unsigned_ratio(self.rate_segment_by(playback, |r| r.playback).rate)
+    }
+
+    /// Returns song seconds at specified playback seconds. Panics if seconds overflow.
+    pub fn song_seconds_at(&self, playback: Ratio<isize>) -> Ratio<isize> {
+        self.try_song_seconds_at(playback)
+            .expect("song seconds overflow")
+    }
+
+    /// Returns song seconds at specified playback seconds.
+    pub fn try_song_seconds_at(
+        &self,
+        playback: Ratio<isize>,
+    ) -> Result<Ratio<isize>, PreintegralError> {
+        let segment = self.rate_segment_by(playback, |r| r.playback);
+        playback
+            .checked_sub(&segment.playback)
+            .and_then(|s| s.checked_mul(&segment.rate))
+            .and_then(|s| s.checked_add(&segment.song))
+            .ok_or(PreintegralError::Overflow)
+    }
+
+    /// Returns playback seconds at specified song seconds. Panics if seconds overflow.
+    pub fn playback_seconds_at(&self, song: Ratio<isize>) -> Ratio<isize> {
+        self.try_playback_seconds_at(song)
+            .expect("playback seconds overflow")
+    }
+
+    /// Returns playback seconds at specified song seconds.
+    pub fn try_playback_seconds_at(
+        &self,
+        song: Ratio<isize>,
+    ) -> Result<Ratio<isize>, PreintegralError> {
+        let segment = self.rate_segment_by(song, |r| r.song);
+        song.checked_sub(&segment.song)
+            .and_then(|s| s.checked_div(&segment.rate))
+            .and_then(|s| s.checked_add(&segment.playback))
+            .ok_or(PreintegralError::Overflow)
+    }
+
+    /// Returns playback seconds at which the note at specified time should be hit.
+    /// Hit windows apply to this value, so they stay in real seconds under any rate.
+    /// Panics if seconds overflow.
+    pub fn judgment_seconds_at(&self, time: Instant) -> Ratio<isize> {
+        self.try_judgment_seconds_at(time)
+            .expect("playback seconds overflow")
+    }
+
+    /// Returns playback seconds at which the note at specified time should be hit.
+    pub fn try_judgment_seconds_at(&self, time: Instant) -> Result<Ratio<isize>, PreintegralError> {
+        let overflow = || PreintegralError::Overflow;
+        let song = to_signed(&self.try_seconds_at(time)?)
+            .and_then(|s| s.checked_add(&self.offsets.chart))
+            .ok_or_else(overflow)?;
+        self.try_playback_seconds_at(song)?
+            .checked_add(&self.offsets.audio)
+            .ok_or_else(overflow)
+    }
+
+    /// Returns chart time to judge at specified playback seconds,
+    /// or `None` before the chart starts. Panics if seconds overflow.
+    pub fn judgment_instant_at(&self, playback: Ratio<isize>) -> Option<Instant> {
+        self.try_judgment_instant_at(playback)
+            .expect("chart time overflow")
+    }
+
+    /// Returns chart time to judge at specified playback seconds,
+    /// or `None` before the chart starts.
+    pub fn try_judgment_instant_at(
+        &self,
+        playback: Ratio<isize>,
+    ) -> Result<Option<Instant>, PreintegralError> {
+        self.chart_instant_at(playback, self.offsets.audio)
+    }
+
+    /// Returns chart time to render at specified playback seconds.
+    /// Stays at zero before the chart starts. Panics if seconds overflow.
+    pub fn render_instant_at(&self, playback: Ratio<isize>) -> Instant {
+        self.try_render_instant_at(playback)
+            .expect("chart time overflow")
+    }
+
+    /// Returns chart time to render at specified playback seconds.
+    /// Stays at zero before the chart starts.
+    pub fn try_render_instant_at(
+        &self,
+        playback: Ratio<isize>,
+    ) -> Result<Instant, PreintegralError> {
+        Ok(self
+            .chart_instant_at(playback, self.offsets.visual)?
+            .unwrap_or_else(Instant::zero))
+    }
+
+    /// Returns chart time at specified playback seconds shifted by user offset,
+    /// or `None` before the chart starts.
+    fn chart_instant_at(
+        &self,
+        playback: Ratio<isize>,
+        user_offset: Ratio<isize>,
+    ) -> Result<Option<Instant>, PreintegralError> {
+        let overflow = || PreintegralError::Overflow;
+        let playback = playback.checked_sub(&user_offset).ok_or_else(overflow)?;
+        let seconds = self
+            .try_song_seconds_at(playback)?
+            .checked_sub(&self.offsets.chart)
+            .ok_or_else(overflow)?;
+        if seconds < Ratio::zero() {
+            return Ok(None);
+        }
+        let seconds = from_signed(seconds).ok_or_else(overflow)?;
+        self.try_instant_at(seconds).map(Some)
+    }
+
+    /// Returns the rate segment containing specified seconds, measured by `key`.
+    fn rate_segment_by(
+        &self,
+        seconds: Ratio<isize>,
+        key: impl Fn(&RateSegment) -> Ratio<isize>,
+    ) -> RateSegment {
+        let index = self.rates.partition_point(|r| key(r) <= seconds).max(1);
+        self.rates[index - 1]
+    }
+
+    /// Returns elapsed seconds at specified time, adding stops before specified index.
+    fn stopped_seconds(&self, time: Instant, stops: usize) -> Result<Ratio<N>, PreintegralError> {
+        self.rhythm
+            .try_fetch(time)?
+            .checked_add(&self.stop_offsets[stops])
+            .ok_or(PreintegralError::Overflow)
+    }
+
+    fn stop_duration(&self, index: usize) -> Ratio<N> {
+        self.stop_offsets[index + 1].clone() - self.stop_offsets[index].clone()
+    }
+}
+
+/// Converts elapsed seconds into signed seconds, or returns `None` if `isize` cannot represent it.
+fn to_signed<N>(seconds: &Ratio<N>) -> Option<Ratio<isize>>
+where
+    N: Clone + Integer + ToPrimitive,
+{
+    Some(Ratio::new(
+        seconds.numer().to_isize()?,
+        seconds.denom().to_isize()?,
+    ))
+}
+
+/// Converts non-negative signed seconds into elapsed seconds,
+/// or returns `None` if `N` cannot represent it.
+fn from_signed<N>(seconds: Ratio<isize>) -> Option<Ratio<N>>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    Some(Ratio::new(
+        N::from_isize(*seconds.numer())?,
+        N::from_isize(*seconds.denom())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChartClock, Offsets};
+    use crate::{
+        instant,
+        preintegral::PreintegralError,
+        time::Instant,
+        timeline,
+        timeline::Timeline,
+        value::{Beat, RhythmChange, Stop, Tempo},
+    };
+
+    use num::{rational::Ratio, BigInt, FromPrimitive, Zero};
+
+    #[test]
+    fn clock_conversion_works() {
+        let clock = ChartClock::new(timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+            [1:1/2]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(240, 1))),
+            [2:0/1]: RhythmChange(Beat(Ratio::new(7, 2)), Tempo(Ratio::new(240, 1))),
+        });
+
+        assert_eq!(clock.seconds_at(instant![1:0/1]), Ratio::new(2, 1));
+        assert_eq!(clock.seconds_at(instant![1:1/2]), Ratio::new(3, 1));
+        assert_eq!(clock.seconds_at(instant![2:0/1]), Ratio::new(7, 2));
+        assert_eq!(clock.seconds_at(instant![3:0/1]), Ratio::new(35, 8));
+
+        for time in [
+            instant![0:0/1],
+            instant![0:3/7],
+            instant![1:1/2],
+            instant![1:3/4],
+            instant![2:1/3],
+            instant![9:0/1],
+        ] {
+            assert_eq!(
+                clock.instant_at(clock.seconds_at(time)),
+                time,
+                "round trip is exact"
+            );
+        }
+    }
+
+    #[test]
+    fn clock_stops_work() {
+        let clock = ChartClock::new(timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+            [1:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(240, 1))),
+        })
+        .with_stops(timeline! {
+            [0:1/2]: Stop(Ratio::new(2, 1)),
+            [1:0/1]: Stop(Ratio::new(4, 1)),
+        });
+
+        assert_eq!(clock.seconds_at(instant![0:1/2]), Ratio::new(1, 1));
+        assert_eq!(clock.seconds_after(instant![0:1/2]), Ratio::new(2, 1));
+        assert_eq!(clock.seconds_at(instant![1:0/1]), Ratio::new(3, 1));
+        assert_eq!(clock.seconds_after(instant![1:0/1]), Ratio::new(4, 1));
+        assert_eq!(clock.seconds_at(instant![1:1/2]), Ratio::new(9, 2));
+
+        assert_eq!(
+            clock.instant_at(Ratio::new(3, 2)),
+            instant![0:1/2],
+            "stopping"
+        );
+        assert_eq!(clock.instant_at(Ratio::new(5, 2)), instant![0:3/4]);
+        assert_eq!(
+            clock.instant_at(Ratio::new(7, 2)),
+            instant![1:0/1],
+            "stopping"
+        );
+        assert_eq!(clock.instant_at(Ratio::new(9, 2)), instant![1:1/2]);
+    }
+
+    #[test]
+    fn clock_offsets_work() {
+        let clock = ChartClock::new(timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        })
+        .with_offsets(Offsets {
+            chart: Ratio::new(1, 2),
+            audio: Ratio::new(1, 10),
+            visual: Ratio::new(-1, 20),
+        });
+
+        assert_eq!(
+            clock.judgment_seconds_at(instant![1:0/1]),
+            Ratio::new(13, 5)
+        );
+        assert_eq!(
+            clock.judgment_instant_at(Ratio::new(13, 5)),
+            Some(instant![1:0/1])
+        );
+        assert_eq!(clock.judgment_instant_at(Ratio::new(1, 2)), None, "lead-in");
+        assert_eq!(clock.render_instant_at(Ratio::new(49, 20)), instant![1:0/1]);
+        assert_eq!(clock.render_instant_at(Ratio::zero()), instant![0:0/1]);
+        assert_eq!(
+            clock.judgment_instant_at(Ratio::new(-3, 1)),
+            None,
+            "before playback starts"
+        );
+        assert_eq!(clock.render_instant_at(Ratio::new(-3, 1)), instant![0:0/1]);
+
+        let overflowing = clock.clone().with_offsets(Offsets {
+            chart: Ratio::from_integer(isize::MAX),
+            ..Offsets::default()
+        });
+        assert_eq!(
+            overflowing.try_judgment_seconds_at(instant![1:0/1]),
+            Err(PreintegralError::Overflow),
+            "fails instead of wrapping"
+        );
+    }
+
+    #[test]
+    fn wide_clock_offsets_work() {
+        let big = |n| BigInt::from_usize(n).unwrap();
+        let mut clock = ChartClock::<BigInt>::new(timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::from_integer(big(4))), Tempo(Ratio::from_integer(big(120)))),
+        })
+        .with_rate(Ratio::new(1, 2))
+        .with_offsets(Offsets {
+            chart: Ratio::new(1, 1),
+            audio: Ratio::new(1, 10),
+            visual: Ratio::zero(),
+        });
+
+        assert_eq!(
+            clock.judgment_seconds_at(instant![1:0/1]),
+            Ratio::new(61, 10)
+        );
+        clock.change_rate(Ratio::new(4, 1), Ratio::new(2, 1));
+        assert_eq!(clock.render_instant_at(Ratio::new(9, 2)), instant![1:0/1]);
+        assert_eq!(clock.judgment_instant_at(Ratio::new(1, 1)), None, "lead-in");
+        assert_eq!(clock.render_instant_at(Ratio::new(1, 1)), instant![0:0/1]);
+    }
+
+    #[test]
+    fn clock_rate_works() {
+        let mut clock = ChartClock::new(timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        })
+        .with_rate(Ratio::new(1, 2))
+        .with_offsets(Offsets {
+            chart: Ratio::new(1, 1),
+            audio: Ratio::new(1, 10),
+            visual: Ratio::zero(),
+        });
+
+        // 3 song seconds take 6 playback seconds, then audio offset in real seconds
+        assert_eq!(
+            clock.judgment_seconds_at(instant![1:0/1]),
+            Ratio::new(61, 10)
+        );
+        assert_eq!(clock.render_instant_at(Ratio::new(6, 1)), instant![1:0/1]);
+
+        // ramp up to 2x at playback 4s, song 2s, chart 1s
+        assert!(!clock.has_rate_changes());
+        clock.change_rate(Ratio::new(4, 1), Ratio::new(2, 1));
+        assert!(clock.has_rate_changes());
+        assert_eq!(clock.render_instant_at(Ratio::new(4, 1)), instant![0:1/2]);
+        assert_eq!(clock.render_instant_at(Ratio::new(9, 2)), instant![1:0/1]);
+        assert_eq!(clock.rate_at(Ratio::new(9, 2)), Ratio::new(2, 1));
+        assert_eq!(
+            clock.judgment_seconds_at(instant![1:0/1]),
+            Ratio::new(46, 10)
+        );
+        for time in [instant![0:1/8], instant![0:3/8], instant![2:1/3]] {
+            assert_eq!(
+                clock.judgment_instant_at(clock.judgment_seconds_at(time)),
+                Some(time),
+                "continuous across rate change"
+            );
+        }
+    }
+
+    #[test]
+    fn wide_clock_works() {
+        // coprime tempos make the denominator of elapsed seconds grow quickly
+        const PRIMES: [usize; 16] = [
+            1601, 1607, 1609, 1613, 1619, 1621, 1627, 1637, 1657, 1663, 1667, 1669, 1693, 1697,
+            1699, 1709,
+        ];
+        fn rhythm<N>() -> Timeline<Instant, RhythmChange<N>>
+        where
+            N: Clone + num::Integer + FromPrimitive,
+        {
+            let ratio = |n, d| Ratio::new(N::from_usize(n).unwrap(), N::from_usize(d).unwrap());
+            PRIMES
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    let change = RhythmChange(Beat(ratio(4, 1)), Tempo(ratio(p, 10)));
+                    (Instant::new_parts(i, 0, 1), change)
+                })
+                .collect()
+        }
+
+        assert_eq!(
+            ChartClock::<usize>::try_new(rhythm()).unwrap_err(),
+            PreintegralError::Overflow
+        );
+        let narrow: ChartClock =
+            ChartClock::try_new(rhythm().into_pairs().take(6).collect()).unwrap();
+        assert_eq!(
+            narrow.try_seconds_at(instant![5:1/9973]),
+            Err(PreintegralError::Overflow),
+            "fails instead of wrapping"
+        );
+        assert_eq!(
+            narrow
+                .try_with_stops(timeline! { [10:1/3]: Stop(Ratio::new(1, 1)), })
+                .unwrap_err(),
+            PreintegralError::Overflow
+        );
+
+        let wide = ChartClock::<BigInt>::new(rhythm())
+            .with_stops(timeline! { [15:1/3]: Stop(Ratio::new(1, 1)), });
+        for time in [
+            instant![0:1/3],
+            instant![11:0/1],
+            instant![15:1/3],
+            instant![15:1/2],
+            instant![200:1/7],
+        ] {
+            assert_eq!(wide.instant_at(wide.seconds_at(time)), time);
+        }
+        assert_eq!(
+            wide.seconds_after(instant![15:1/3]) - wide.seconds_at(instant![15:1/3]),
+            Ratio::new(BigInt::from(600), BigInt::from(1709)),
+            "stop resolved by tempo"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Contains deterministic difficulty estimation.
+//!
+//! Ratings are exact, so the same chart always gets the same rating
+//! regardless of its declared level.
+
+use crate::{
+    analysis::ChartStats,
+    bpm::BpmStats,
+    chart::Chart,
+    clock::ChartClock,
+    pattern::{recognize, PatternKind},
+    time::{Instant, Seconds},
+};
+
+use num::{rational::Ratio, One};
+
+/// Length of density windows, in seconds.
+const DENSITY_WINDOW: usize = 2;
+
+/// Represents ratings of each skill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SkillRatings {
+    /// Peak notes per second.
+    pub density: Ratio<usize>,
+
+    /// The most intense jack.
+    pub jack: Ratio<usize>,
+
+    /// The most intense trill, roll or jumpstream.
+    pub stream: Ratio<usize>,
+
+    /// The most intense chordjack.
+    pub chord: Ratio<usize>,
+
+    /// The most intense long-note section.
+    pub long_note: Ratio<usize>,
+
+    /// Tempo deviation until the last note above constant tempo; see `BpmStats::deviation`.
+    pub soflan: Ratio<usize>,
+}
+
+/// Represents weights of skills in the rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifficultyWeights {
+    pub density: Ratio<usize>,
+    pub jack: Ratio<usize>,
+    pub stream: Ratio<usize>,
+    pub chord: Ratio<usize>,
+    pub long_note: Ratio<usize>,
+    pub soflan: Ratio<usize>,
+}
+
+impl Default for DifficultyWeights {
+    fn default() -> Self {
+        DifficultyWeights {
+            density: Ratio::from_integer(1),
+            jack: Ratio::new(1, 2),
+            stream: Ratio::new(1, 4),
+            chord: Ratio::new(1, 2),
+            long_note: Ratio::new(1, 4),
+            soflan: Ratio::from_integer(1),
+        }
+    }
+}
+
+/// Represents an estimated difficulty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difficulty {
+    /// Weighted sum of skill ratings.
+    pub rating: Ratio<usize>,
+    pub skills: SkillRatings,
+}
+
+impl SkillRatings {
+    /// Rates skills of the chart.
+    pub fn new(chart: &Chart, clock: &ChartClock) -> SkillRatings {
+        let window = Seconds(Ratio::from_integer(DENSITY_WINDOW));
+        let stats = ChartStats::new(chart, clock, window);
+        let patterns = recognize(chart, clock);
+        let strain = |kinds: &[PatternKind]| {
+            patterns
+                .spans()
+                .filter(|(_, _, p)| kinds.contains(&p.kind))
+                .map(|(_, _, p)| p.intensity)
+                .max()
+                .unwrap_or_default()
+        };
+
+        let end = chart.last_note_time().unwrap_or_else(Instant::zero);
+        let soflan = BpmStats::new(clock, end).deviation() - Ratio::one();
+
+        SkillRatings {
+            density: stats.peak.map(|peak| peak.nps).unwrap_or_default(),
+            jack: strain(&[PatternKind::Jack]),
+            stream: strain(&[
+                PatternKind::Trill,
+                PatternKind::Roll,
+                PatternKind::Jumpstream,
+            ]),
+            chord: strain(&[PatternKind::Chordjack]),
+            long_note: strain(&[PatternKind::LongNote]),
+            soflan,
+        }
+    }
+}
+
+impl Difficulty {
+    /// Estimates difficulty of the chart with specified weights.
+    pub fn estimate(chart: &Chart, clock: &ChartClock, weights: &DifficultyWeights) -> Difficulty {
+        let skills = SkillRatings::new(chart, clock);
+        let rating = weights.density * skills.density
+            + weights.jack * skills.jack
+            + weights.stream * skills.stream
+            + weights.chord * skills.chord
+            + weights.long_note * skills.long_note
+            + weights.soflan * skills.soflan;
+        Difficulty { rating, skills }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difficulty, DifficultyWeights, SkillRatings};
+    use crate::{
+        chart::Chart,
+        clock::ChartClock,
+        fixture, instant,
+        note::{LaneKind, NoteKind},
+        time::Instant,
+        value::{Beat, RhythmChange, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    /// Returns a jack of 8 notes in measure 1, which changes tempo from 120 BPM.
+    fn jacks(tempo: usize) -> (Chart, ChartClock) {
+        let mut rhythm = fixture::rhythm(120);
+        rhythm.append(
+            instant![1:0/1],
+            RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::from_integer(tempo))),
+        );
+        let notes: Vec<_> = (0..8)
+            .map(|i| (Instant::new_parts(1, i, 8), 0, NoteKind::Tap))
+            .collect();
+        fixture::chart_with(rhythm, vec![LaneKind::Key; 4], &notes)
+    }
+
+    #[test]
+    fn difficulty_estimation_works() {
+        let (chart, clock) = jacks(120);
+        let weights = DifficultyWeights::default();
+        let difficulty = Difficulty::estimate(&chart, &clock, &weights);
+        // 8 notes over 2 seconds
+        assert_eq!(difficulty.skills.density, Ratio::from_integer(4));
+        assert_eq!(difficulty.skills.jack, Ratio::from_integer(4));
+        assert_eq!(difficulty.skills.soflan, Ratio::from_integer(0));
+        assert_eq!(difficulty.rating, Ratio::from_integer(6));
+        assert_eq!(
+            difficulty,
+            Difficulty::estimate(&chart, &clock, &weights),
+            "deterministic"
+        );
+
+        // 2 seconds at main BPM 120 and 7/8 seconds at 240 until the last note
+        let (fast, fast_clock) = jacks(240);
+        let faster = Difficulty::estimate(&fast, &fast_clock, &weights);
+        assert_eq!(faster.skills.soflan, Ratio::new(7, 23));
+        assert_eq!(faster.skills.jack, Ratio::from_integer(8));
+        assert!(faster.rating > difficulty.rating);
+
+        let jack_only = DifficultyWeights {
+            density: Ratio::from_integer(0),
+            soflan: Ratio::from_integer(0),
+            ..weights
+        };
+        assert_eq!(
+            Difficulty::estimate(&chart, &clock, &jack_only).rating,
+            Ratio::from_integer(2)
+        );
+    }
+
+    #[test]
+    fn difficulty_of_edge_charts_works() {
+        let weights = DifficultyWeights::default();
+        let (empty, clock) = fixture::chart(4, &[]);
+        let difficulty = Difficulty::estimate(&empty, &clock, &weights);
+        assert_eq!(difficulty.skills, SkillRatings::default());
+        assert_eq!(difficulty.rating, Ratio::from_integer(0));
+
+        // a single note at the end of the chart; 1 note over a 2 seconds window
+        let (single, clock) = fixture::chart(4, &[(instant![1:0/1], 0, NoteKind::Tap)]);
+        let difficulty = Difficulty::estimate(&single, &clock, &weights);
+        assert_eq!(
+            difficulty.skills,
+            SkillRatings {
+                density: Ratio::new(1, 2),
+                ..Default::default()
+            }
+        );
+        assert_eq!(difficulty.rating, Ratio::new(1, 2));
+    }
+
+    #[test]
+    fn short_tempo_spike_rates_lower_than_sustained_change() {
+        let tempo = |bpm| RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::from_integer(bpm)));
+        let notes: Vec<_> = (0..16)
+            .map(|i| (Instant::new_parts(i / 4, i % 4, 4), 0, NoteKind::Tap))
+            .collect();
+        let estimate = |changes: &[(Instant, usize)]| {
+            let mut rhythm = fixture::rhythm(120);
+            for &(time, bpm) in changes {
+                rhythm.append(time, tempo(bpm));
+            }
+            let (chart, clock) = fixture::chart_with(rhythm, vec![LaneKind::Key; 4], &notes);
+            Difficulty::estimate(&chart, &clock, &DifficultyWeights::default())
+        };
+
+        let spike = estimate(&[(instant![2:0/1], 240), (instant![2:1/4], 120)]);
+        let sustained = estimate(&[(instant![2:0/1], 240)]);
+        assert!(spike.skills.soflan > Ratio::from_integer(0));
+        assert!(spike.skills.soflan < sustained.skills.soflan);
+        assert!(spike.rating < sustained.rating);
+    }
+}
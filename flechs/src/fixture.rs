@@ -0,0 +1,55 @@
+//! Contains fixtures shared by unit tests.
+
+use crate::{
+    chart::Chart,
+    clock::ChartClock,
+    note::{LaneId, LaneKind, Note, NoteKind},
+    time::Instant,
+    timeline::Timeline,
+    value::{Beat, RhythmChange, Tempo},
+};
+
+use num::rational::Ratio;
+
+/// Returns rhythm of 4/4 at specified tempo throughout.
+pub fn rhythm(tempo: usize) -> Timeline<Instant, RhythmChange> {
+    let mut rhythm = Timeline::new();
+    rhythm.append(
+        Instant::zero(),
+        RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::from_integer(tempo))),
+    );
+    rhythm
+}
+
+/// Returns chart of key lanes at 120 BPM, where a measure is 2 seconds, with its clock.
+pub fn chart(lanes: usize, notes: &[(Instant, usize, NoteKind)]) -> (Chart, ChartClock) {
+    chart_with(rhythm(120), vec![LaneKind::Key; lanes], notes)
+}
+
+/// Returns chart of specified rhythm and lanes, with its clock.
+pub fn chart_with(
+    rhythm: Timeline<Instant, RhythmChange>,
+    lane_kinds: Vec<LaneKind>,
+    notes: &[(Instant, usize, NoteKind)],
+) -> (Chart, ChartClock) {
+    let clock = ChartClock::new(rhythm.clone());
+    let mut chart = Chart::with_lanes(rhythm, lane_kinds);
+    insert_notes(&mut chart, notes);
+    (chart, clock)
+}
+
+/// Inserts notes of `(time, lane, kind)`. Panics if any cannot be inserted.
+pub fn insert_notes(chart: &mut Chart, notes: &[(Instant, usize, NoteKind)]) {
+    chart
+        .insert_notes(
+            notes
+                .iter()
+                .map(|&(time, lane, kind)| (time, Note::new(LaneId(lane), kind))),
+        )
+        .unwrap();
+}
+
+/// Returns kind of hold note ending at specified time.
+pub const fn hold(end: Instant) -> NoteKind {
+    NoteKind::Hold { end }
+}
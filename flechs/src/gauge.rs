@@ -0,0 +1,293 @@
+//! Contains life gauges consuming judgments.
+
+use crate::{
+    judge::{Grade, Judgment, JudgmentKind},
+    score::Scoring,
+    time::SignedSeconds,
+    timeline::Timeline,
+    util::{signed_ratio, unsigned_ratio},
+};
+
+use num::rational::Ratio;
+
+/// Represents a kind of gauge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GaugeKind {
+    AssistEasy,
+    Easy,
+    Normal,
+    Hard,
+    ExHard,
+
+    /// Fails on any BAD, POOR or miss.
+    Hazard,
+}
+
+impl GaugeKind {
+    /// Returns whether the gauge starts full and fails at zero.
+    pub const fn is_survival(&self) -> bool {
+        matches!(
+            self,
+            GaugeKind::Hard | GaugeKind::ExHard | GaugeKind::Hazard
+        )
+    }
+
+    /// Returns initial value in percent.
+    pub fn initial(&self) -> Ratio<usize> {
+        if self.is_survival() {
+            Ratio::from_integer(100)
+        } else {
+            Ratio::from_integer(22)
+        }
+    }
+
+    /// Returns value in percent required to clear at the end.
+    pub fn border(&self) -> Ratio<usize> {
+        match self {
+            GaugeKind::AssistEasy => Ratio::from_integer(60),
+            GaugeKind::Easy | GaugeKind::Normal => Ratio::from_integer(80),
+            _ => Ratio::from_integer(0),
+        }
+    }
+}
+
+/// Represents a life gauge.
+#[derive(Debug, Clone)]
+pub struct Gauge {
+    kind: GaugeKind,
+
+    /// Increment of PGREAT and GREAT in groove gauges.
+    increment: Ratio<isize>,
+    value: Ratio<usize>,
+    failed: bool,
+    history: Timeline<SignedSeconds, Ratio<usize>>,
+}
+
+impl Gauge {
+    /// Creates new gauge for specified number of judged notes and releases.
+    /// Groove gauges gain `760.5 / (notes + 650)` percent for each PGREAT and GREAT.
+    pub fn new(kind: GaugeKind, notes: usize) -> Gauge {
+        let notes = isize::try_from(notes).expect("too many notes");
+        Gauge {
+            kind,
+            increment: Ratio::new(1521, 2 * (notes + 650)),
+            value: kind.initial(),
+            failed: false,
+            history: Timeline::new(),
+        }
+    }
+
+    pub fn kind(&self) -> GaugeKind {
+        self.kind
+    }
+
+    /// Returns current value in percent.
+    pub fn value(&self) -> Ratio<usize> {
+        self.value
+    }
+
+    /// Returns whether survival gauge reached zero.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Returns whether the gauge clears if the play ends now.
+    pub fn is_cleared(&self) -> bool {
+        !self.failed && self.value >= self.kind.border()
+    }
+
+    /// Returns value after each judgment, at the seconds of judgment in order of judgments.
+    pub fn history(&self) -> &Timeline<SignedSeconds, Ratio<usize>> {
+        &self.history
+    }
+
+    /// Returns the change of value in percent, before relief.
+    fn delta(&self, kind: JudgmentKind) -> Ratio<isize> {
+        let grade = match kind {
+            JudgmentKind::Hit(grade) | JudgmentKind::Release(grade) => Some(grade),
+            JudgmentKind::Miss | JudgmentKind::ReleaseMiss => Some(Grade::Poor),
+            JudgmentKind::EmptyPoor => None,
+            JudgmentKind::HoldTick { held: true } => return Ratio::from_integer(0),
+            // unheld tick drains half of empty POOR
+            JudgmentKind::HoldTick { held: false } => {
+                return self.delta(JudgmentKind::EmptyPoor) / 2
+            }
+        };
+
+        let gain = if self.kind.is_survival() {
+            Ratio::new(16, 100)
+        } else {
+            self.increment
+        };
+        // damage of BAD, POOR and empty POOR, and its scale
+        let ([bad, poor, empty_poor], scale) = match self.kind {
+            GaugeKind::AssistEasy => ([2, 6, 2], Ratio::new(3, 5)),
+            GaugeKind::Easy => ([2, 6, 2], Ratio::new(4, 5)),
+            GaugeKind::Normal => ([2, 6, 2], Ratio::from_integer(1)),
+            GaugeKind::Hard => ([6, 10, 2], Ratio::from_integer(1)),
+            GaugeKind::ExHard => ([12, 20, 10], Ratio::from_integer(1)),
+            GaugeKind::Hazard => ([100, 100, 10], Ratio::from_integer(1)),
+        };
+        match (self.kind, grade) {
+            (_, Some(Grade::PGreat | Grade::Great)) => gain,
+            (GaugeKind::Hard | GaugeKind::ExHard | GaugeKind::Hazard, Some(Grade::Good)) => {
+                Ratio::from_integer(0)
+            }
+            (_, Some(Grade::Good)) => gain / 2,
+            (_, Some(Grade::Bad)) => Ratio::from_integer(-bad) * scale,
+            (_, Some(Grade::Poor)) => Ratio::from_integer(-poor) * scale,
+            (_, None) => Ratio::from_integer(-empty_poor) * scale,
+        }
+    }
+}
+
+impl Scoring for Gauge {
+    fn judge(&mut self, judgment: &Judgment) {
+        if self.failed {
+            return;
+        }
+
+        let mut delta = self.delta(judgment.kind);
+        // hard gauge halves damage under 30%
+        if self.kind == GaugeKind::Hard
+            && self.value < Ratio::from_integer(30)
+            && delta < Ratio::from_integer(0)
+        {
+            delta /= 2;
+        }
+
+        let value = (signed_ratio(self.value) + delta)
+            .max(Ratio::from_integer(0))
+            .min(Ratio::from_integer(100));
+        self.value = unsigned_ratio(value);
+        if self.kind.is_survival() && self.value == Ratio::from_integer(0) {
+            self.failed = true;
+        }
+        self.history.insert(judgment.seconds, self.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gauge, GaugeKind};
+    use crate::{
+        instant,
+        judge::{Grade, Judgment, JudgmentKind},
+        note::LaneId,
+        score::Scoring,
+        time::{Instant, SignedSeconds},
+    };
+
+    use num::rational::Ratio;
+
+    fn judgment(lane: usize, time: Instant, seconds: Ratio<isize>, kind: JudgmentKind) -> Judgment {
+        Judgment {
+            lane: LaneId(lane),
+            time,
+            kind,
+            seconds: SignedSeconds(seconds),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn gauges_work() {
+        let judgments = [
+            judgment(
+                0,
+                instant![0:0/1],
+                Ratio::from_integer(0),
+                JudgmentKind::Hit(Grade::PGreat),
+            ),
+            judgment(
+                0,
+                instant![0:1/2],
+                Ratio::from_integer(1),
+                JudgmentKind::Hit(Grade::Good),
+            ),
+            judgment(
+                0,
+                instant![1:0/1],
+                Ratio::from_integer(2),
+                JudgmentKind::Miss,
+            ),
+            judgment(
+                0,
+                instant![1:1/2],
+                Ratio::from_integer(3),
+                JudgmentKind::HoldTick { held: false },
+            ),
+        ];
+
+        let mut normal = Gauge::new(GaugeKind::Normal, 350);
+        normal.judge_all(&judgments);
+        // 760.5 / 1000 gain per GREAT
+        let gain = Ratio::new(1521, 2000);
+        assert_eq!(
+            normal.value(),
+            Ratio::from_integer(22) + gain + gain / 2 - Ratio::from_integer(7)
+        );
+        assert_eq!(normal.history().len(), 4);
+        assert_eq!(
+            normal
+                .history()
+                .latest_item(SignedSeconds(Ratio::new(3, 2))),
+            Some(&(Ratio::from_integer(22) + gain + gain / 2))
+        );
+        assert!(!normal.is_cleared());
+
+        let mut hard = Gauge::new(GaugeKind::Hard, 350);
+        hard.judge_all(&judgments);
+        assert_eq!(hard.value(), Ratio::from_integer(89));
+        assert!(hard.is_cleared());
+
+        let mut hazard = Gauge::new(GaugeKind::Hazard, 350);
+        hazard.judge_all(&judgments);
+        assert!(hazard.is_failed());
+        assert_eq!(hazard.history().len(), 3, "stops at failure");
+    }
+
+    #[test]
+    fn gauge_history_is_in_order_of_judgments() {
+        // the miss of an earlier note is judged after the chord
+        let judgments = [
+            judgment(
+                0,
+                instant![1:0/1],
+                Ratio::from_integer(2),
+                JudgmentKind::Hit(Grade::PGreat),
+            ),
+            judgment(
+                1,
+                instant![1:0/1],
+                Ratio::from_integer(2),
+                JudgmentKind::Hit(Grade::Great),
+            ),
+            judgment(2, instant![0:7/8], Ratio::new(9, 4), JudgmentKind::Miss),
+        ];
+        let mut normal = Gauge::new(GaugeKind::Normal, 350);
+        normal.judge_all(&judgments);
+
+        let gain = Ratio::new(1521, 2000);
+        let initial = Ratio::from_integer(22);
+        let history: Vec<_> = normal.history().pairs().map(|(s, v)| (s.0, *v)).collect();
+        assert_eq!(
+            history,
+            vec![
+                (Ratio::from_integer(2), initial + gain),
+                (Ratio::from_integer(2), initial + gain * 2),
+                (
+                    Ratio::new(9, 4),
+                    initial + gain * 2 - Ratio::from_integer(6)
+                ),
+            ]
+        );
+        assert_eq!(
+            normal
+                .history()
+                .latest_item(SignedSeconds(Ratio::from_integer(2))),
+            Some(&(initial + gain * 2)),
+            "after the whole chord"
+        );
+    }
+}
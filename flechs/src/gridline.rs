@@ -0,0 +1,169 @@
+//! Contains measure-line and beat-line generation.
+
+use crate::{
+    preintegral::Preintegral,
+    time::Instant,
+    timeline::Timeline,
+    value::{Beat, ScrollChange},
+};
+
+use num::{integer::div_ceil, rational::Ratio};
+
+/// Specifies which lines are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineDensity {
+    /// Measure lines only.
+    Measures,
+
+    /// Measure lines and beat lines.
+    Beats,
+
+    /// Measure lines, beat lines and specified number of grid lines per beat (including beat line).
+    Grid(usize),
+}
+
+/// Represents a kind of line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LineKind {
+    Measure,
+    Beat,
+    Grid,
+}
+
+/// Iterator of lines over beat timeline.
+#[derive(Debug, Clone)]
+pub struct GridLines<'a> {
+    beats: &'a Timeline<usize, Beat>,
+    end: Instant,
+    divisions: usize,
+    measure: usize,
+    index: usize,
+}
+
+impl<'a> GridLines<'a> {
+    /// Creates new iterator yielding lines at or before `end`.
+    /// Beat timeline must be zero-aligned.
+    pub fn new(beats: &'a Timeline<usize, Beat>, end: Instant, density: LineDensity) -> Self {
+        let divisions = match density {
+            LineDensity::Measures => 0,
+            LineDensity::Beats => 1,
+            LineDensity::Grid(n) => n.max(1),
+        };
+        GridLines {
+            beats,
+            end,
+            divisions,
+            measure: 0,
+            index: 0,
+        }
+    }
+
+    /// Maps lines into scroll positions.
+    pub fn positioned(
+        self,
+        scroll: &'a Preintegral<Instant, ScrollChange>,
+    ) -> impl Iterator<Item = (Instant, LineKind, Ratio<isize>)> + 'a {
+        self.map(|(time, kind)| (time, kind, scroll.fetch(time)))
+    }
+}
+
+impl<'a> Iterator for GridLines<'a> {
+    type Item = (Instant, LineKind);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Beat(beat) = *self
+            .beats
+            .latest_item(self.measure)
+            .expect("must be zero-aligned");
+
+        // the number of lines in measure; ceil(beat * divisions)
+        let scaled = beat * self.divisions;
+        let lines = div_ceil(*scaled.numer(), *scaled.denom()).max(1);
+        if self.index >= lines {
+            self.measure += 1;
+            self.index = 0;
+            return self.next();
+        }
+
+        let (time, kind) = if self.index == 0 {
+            (Instant::new_parts(self.measure, 0, 1), LineKind::Measure)
+        } else {
+            let submeasure = Ratio::new(self.index, self.divisions) / beat;
+            let kind = if self.index.is_multiple_of(self.divisions) {
+                LineKind::Beat
+            } else {
+                LineKind::Grid
+            };
+            (
+                Instant::new(self.measure, submeasure).expect("must be in measure"),
+                kind,
+            )
+        };
+        if time > self.end {
+            return None;
+        }
+
+        self.index += 1;
+        Some((time, kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GridLines, LineDensity, LineKind};
+    use crate::{
+        instant,
+        preintegral::Preintegral,
+        timeline,
+        value::{merge_beats_and_scroll, Beat, Scroll},
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn fractional_measure_lines_work() {
+        let beats = timeline! {
+            [0]: Beat(Ratio::new(7, 2)),
+            [1]: Beat(Ratio::new(2, 1)),
+        };
+
+        let lines: Vec<_> = GridLines::new(&beats, instant![2:0/1], LineDensity::Beats).collect();
+        assert_eq!(
+            lines,
+            vec![
+                (instant![0:0/1], LineKind::Measure),
+                (instant![0:2/7], LineKind::Beat),
+                (instant![0:4/7], LineKind::Beat),
+                (instant![0:6/7], LineKind::Beat),
+                (instant![1:0/1], LineKind::Measure),
+                (instant![1:1/2], LineKind::Beat),
+                (instant![2:0/1], LineKind::Measure),
+            ]
+        );
+
+        let lines: Vec<_> = GridLines::new(&beats, instant![1:1/2], LineDensity::Grid(2)).collect();
+        assert_eq!(
+            lines.len(),
+            7 + 3,
+            "7 lines in 3.5 beats, 3 lines until 1:1/2"
+        );
+        assert_eq!(lines[1], (instant![0:1/7], LineKind::Grid));
+        assert_eq!(lines[9], (instant![1:1/2], LineKind::Beat));
+
+        let scroll = Preintegral::new(
+            merge_beats_and_scroll(
+                beats.clone(),
+                timeline! { [0:0/1]: Scroll(Ratio::new(2, 1)), },
+            )
+            .unwrap(),
+        );
+        let positions: Vec<_> = GridLines::new(&beats, instant![2:0/1], LineDensity::Measures)
+            .positioned(&scroll)
+            .map(|(_, _, p)| p)
+            .collect();
+        assert_eq!(
+            positions,
+            vec![Ratio::new(0, 1), Ratio::new(7, 1), Ratio::new(11, 1)]
+        );
+    }
+}
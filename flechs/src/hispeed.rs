@@ -0,0 +1,260 @@
+//! Contains floating hi-speed controller.
+
+use crate::{
+    bpm::BpmStats,
+    chart::Chart,
+    clock::ChartClock,
+    preintegral::Preintegral,
+    render::RenderSettings,
+    time::Instant,
+    timeline::{Timeline, TimelineError},
+    util::signed_ratio,
+    value::{merge_beats_and_scroll, Beat, RhythmChange, Scroll, ScrollChange, Tempo},
+};
+
+use num::{rational::Ratio, One};
+
+/// Specifies which BPM floating hi-speed keeps the visible duration for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BpmFix {
+    /// BPM at the start of chart.
+    Start,
+
+    /// Minimum BPM.
+    Min,
+
+    /// Maximum BPM.
+    Max,
+
+    /// BPM which lasts longest in wall-clock time.
+    Main,
+}
+
+/// Represents hi-speed mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HiSpeedMode {
+    /// Keeps visible duration at the BPM specified by `BpmFix`.
+    Floating(BpmFix),
+
+    /// Scrolls by elapsed seconds, ignoring BPM entirely.
+    Regul,
+}
+
+/// Computes hi-speed multiplier from green number (visible duration in milliseconds).
+#[derive(Debug, Clone)]
+pub struct HiSpeedController {
+    mode: HiSpeedMode,
+    green_number: Ratio<usize>,
+    lane_length: Ratio<usize>,
+    reference_tempo: Tempo,
+}
+
+impl HiSpeedController {
+    /// Creates new controller.
+    /// `lane_length` is the visible lane length in scroll position (beats at 1x hi-speed).
+    pub fn new(
+        chart: &Chart,
+        clock: &ChartClock,
+        mode: HiSpeedMode,
+        green_number: Ratio<usize>,
+        lane_length: Ratio<usize>,
+    ) -> HiSpeedController {
+        let reference_tempo = match mode {
+            HiSpeedMode::Floating(fix) => {
+                let end = chart.length(clock, |_| None).end;
+                let stats = BpmStats::new(clock, end);
+                match fix {
+                    BpmFix::Start => stats.start,
+                    BpmFix::Min => stats.min,
+                    BpmFix::Max => stats.max,
+                    BpmFix::Main => stats.main,
+                }
+            }
+            // scroll position is measured in seconds, as if BPM were 60
+            HiSpeedMode::Regul => Tempo(Ratio::from_integer(60)),
+        };
+
+        HiSpeedController {
+            mode,
+            green_number,
+            lane_length,
+            reference_tempo,
+        }
+    }
+
+    /// Returns hi-speed mode.
+    pub fn mode(&self) -> HiSpeedMode {
+        self.mode
+    }
+
+    /// Returns the BPM whose visible duration is kept.
+    pub fn reference_tempo(&self) -> Tempo {
+        self.reference_tempo
+    }
+
+    /// Returns green number in milliseconds.
+    pub fn green_number(&self) -> Ratio<usize> {
+        self.green_number
+    }
+
+    /// Sets green number in milliseconds.
+    pub fn set_green_number(&mut self, green_number: Ratio<usize>) {
+        self.green_number = green_number;
+    }
+
+    /// Returns hi-speed multiplier which keeps the green number.
+    pub fn multiplier(&self) -> Ratio<usize> {
+        self.lane_length * 60000 / (self.green_number * self.reference_tempo.0)
+    }
+
+    /// Returns settings for render list query.
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            hispeed: self.multiplier(),
+            lane_length: self.lane_length,
+        }
+    }
+
+    /// Returns visible duration in milliseconds at specified BPM with scroll speed 1.
+    pub fn green_number_at(&self, tempo: Tempo) -> Ratio<usize> {
+        match self.mode {
+            HiSpeedMode::Floating(_) => self.green_number * self.reference_tempo.0 / tempo.0,
+            HiSpeedMode::Regul => self.green_number,
+        }
+    }
+
+    /// Builds scroll preintegral to render with.
+    /// In regul mode, scroll speed is divided by BPM so that position advances by seconds.
+    pub fn scroll(
+        &self,
+        clock: &ChartClock,
+        beats: Timeline<usize, Beat>,
+        scrolls: Timeline<Instant, Scroll>,
+    ) -> Result<Preintegral<Instant, ScrollChange>, TimelineError> {
+        match self.mode {
+            HiSpeedMode::Floating(_) => {
+                Ok(Preintegral::new(merge_beats_and_scroll(beats, scrolls)?))
+            }
+            HiSpeedMode::Regul => {
+                let rhythm: Timeline<_, _> = clock
+                    .rhythm()
+                    .sections()
+                    .map(|(time, rhythm, _)| (time, *rhythm))
+                    .collect();
+                let mut last_rhythm = None;
+                let mut last_scroll = Ratio::one();
+                let regul = rhythm
+                    .merge(scrolls)?
+                    .into_pairs()
+                    .map(|(time, (rhythm, scroll))| {
+                        last_rhythm = rhythm.or(last_rhythm);
+                        last_scroll = scroll.map_or(last_scroll, |s| s.0);
+                        let RhythmChange(beat, tempo) =
+                            last_rhythm.ok_or(TimelineError::NotZeroAligned)?;
+                        let per_tempo = signed_ratio(Ratio::from_integer(60) / tempo.0);
+                        Ok((time, ScrollChange(beat, Scroll(last_scroll * per_tempo))))
+                    })
+                    .collect::<Result<Timeline<_, _>, _>>()?;
+                Ok(Preintegral::new(regul))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BpmFix, HiSpeedController, HiSpeedMode};
+    use crate::{
+        chart::Chart,
+        clock::ChartClock,
+        instant,
+        note::{LaneId, Note, NoteKind},
+        timeline,
+        value::{Beat, RhythmChange, Scroll, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    fn chart() -> Chart {
+        let rhythm = timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(150, 1))),
+            [1:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(75, 1))),
+            [2:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(300, 1))),
+        };
+        let mut chart = Chart::new(rhythm, 1);
+        chart
+            .insert_note(instant![3:0/1], Note::new(LaneId(0), NoteKind::Tap))
+            .unwrap();
+        chart
+    }
+
+    #[test]
+    fn fix_modes_work() {
+        let chart = chart();
+        let clock = ChartClock::new(chart.rhythm().clone());
+        let controller = |fix| {
+            HiSpeedController::new(
+                &chart,
+                &clock,
+                HiSpeedMode::Floating(fix),
+                Ratio::new(500, 1),
+                Ratio::new(4, 1),
+            )
+        };
+
+        assert_eq!(
+            controller(BpmFix::Start).reference_tempo().0,
+            Ratio::new(150, 1)
+        );
+        assert_eq!(
+            controller(BpmFix::Min).reference_tempo().0,
+            Ratio::new(75, 1)
+        );
+        assert_eq!(
+            controller(BpmFix::Max).reference_tempo().0,
+            Ratio::new(300, 1)
+        );
+        assert_eq!(
+            controller(BpmFix::Main).reference_tempo().0,
+            Ratio::new(75, 1)
+        );
+
+        // 4 beats at 150 BPM take 1600 ms, so 500 ms needs 3.2x
+        let start = controller(BpmFix::Start);
+        assert_eq!(start.multiplier(), Ratio::new(16, 5));
+        assert_eq!(
+            start.green_number_at(Tempo(Ratio::new(300, 1))),
+            Ratio::new(250, 1)
+        );
+    }
+
+    #[test]
+    fn regul_speed_works() {
+        let chart = chart();
+        let clock = ChartClock::new(chart.rhythm().clone());
+        let controller = HiSpeedController::new(
+            &chart,
+            &clock,
+            HiSpeedMode::Regul,
+            Ratio::new(500, 1),
+            Ratio::new(4, 1),
+        );
+        let scroll = controller
+            .scroll(
+                &clock,
+                timeline! { [0]: Beat(Ratio::new(4, 1)), },
+                timeline! { [0:0/1]: Scroll(Ratio::new(1, 1)), },
+            )
+            .unwrap();
+
+        for time in [instant![0:1/2], instant![1:1/3], instant![2:3/4]] {
+            let seconds = clock.seconds_at(time);
+            assert_eq!(
+                scroll.fetch(time),
+                Ratio::new(*seconds.numer() as isize, *seconds.denom() as isize),
+                "position advances by seconds"
+            );
+        }
+        assert_eq!(controller.multiplier(), Ratio::new(8, 1));
+    }
+}
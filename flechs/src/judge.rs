@@ -0,0 +1,853 @@
+//! Contains headless judgment engine.
+//!
+//! All seconds are playback seconds, given by `ChartClock::judgment_seconds_at`.
+//! Hit windows are in the same real seconds, regardless of playback rate.
+
+use crate::{
+    chart::Chart,
+    clock::ChartClock,
+    note::LaneId,
+    time::{Instant, SignedSeconds},
+    util::signed_ratio,
+};
+
+use num::rational::Ratio;
+
+/// Represents a timing window around the note, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub early: Ratio<usize>,
+    pub late: Ratio<usize>,
+}
+
+impl Window {
+    /// Creates window of the same width on both sides.
+    pub const fn symmetric(width: Ratio<usize>) -> Window {
+        Window {
+            early: width,
+            late: width,
+        }
+    }
+
+    /// Creates symmetric window from milliseconds.
+    pub fn millis(millis: usize) -> Window {
+        Window::symmetric(Ratio::new(millis, 1000))
+    }
+
+    /// Returns whether the timing error (negative if early) is in the window.
+    pub fn contains(&self, error: Ratio<isize>) -> bool {
+        -signed_ratio(self.early) <= error && error <= signed_ratio(self.late)
+    }
+}
+
+/// Represents grade of a hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Grade {
+    PGreat,
+    Great,
+    Good,
+    Bad,
+    Poor,
+}
+
+/// Represents hit windows for each grade. Windows must be nested in grade order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitWindows {
+    pub pgreat: Window,
+    pub great: Window,
+    pub good: Window,
+    pub bad: Window,
+
+    /// Presses in this window hit the note as `Grade::Poor`.
+    /// Same as `bad` if the game has no such hit.
+    pub poor: Window,
+
+    /// Presses in this window but out of `poor` are empty POOR, leaving the note.
+    /// Same as `poor` if the game has no empty POOR.
+    pub empty_poor: Window,
+}
+
+impl HitWindows {
+    /// beatmania IIDX.
+    pub fn iidx() -> HitWindows {
+        let bad = Window::symmetric(Ratio::new(1, 4));
+        HitWindows {
+            pgreat: Window::symmetric(Ratio::new(1, 60)),
+            great: Window::symmetric(Ratio::new(1, 30)),
+            good: Window::symmetric(Ratio::new(7, 60)),
+            bad,
+            poor: bad,
+            empty_poor: Window {
+                early: Ratio::new(1, 2),
+                late: bad.late,
+            },
+        }
+    }
+
+    /// Lunatic Rave 2, NORMAL judge rank.
+    pub fn lr2() -> HitWindows {
+        let bad = Window::millis(200);
+        HitWindows {
+            pgreat: Window::millis(18),
+            great: Window::millis(40),
+            good: Window::millis(100),
+            bad,
+            poor: bad,
+            empty_poor: Window {
+                early: Ratio::from_integer(1),
+                late: bad.late,
+            },
+        }
+    }
+
+    /// beatoraja, NORMAL judge rank.
+    pub fn beatoraja() -> HitWindows {
+        let bad = Window {
+            early: Ratio::new(280, 1000),
+            late: Ratio::new(220, 1000),
+        };
+        HitWindows {
+            pgreat: Window::millis(20),
+            great: Window::millis(60),
+            good: Window::millis(150),
+            bad,
+            poor: bad,
+            empty_poor: Window {
+                early: Ratio::new(500, 1000),
+                late: bad.late,
+            },
+        }
+    }
+
+    /// osu!mania with specified overall difficulty, 0 to 10.
+    /// MAX, 300, 200, 100 and 50 are mapped in order, and the miss window hits as `Grade::Poor`.
+    pub fn osu_mania(od: Ratio<usize>) -> HitWindows {
+        assert!(od <= Ratio::from_integer(10), "invalid overall difficulty");
+        let window = |base: usize| Window::symmetric((Ratio::from_integer(base) - od * 3) / 1000);
+        let miss = window(188);
+        HitWindows {
+            pgreat: Window::millis(16),
+            great: window(64),
+            good: window(97),
+            bad: window(127),
+            poor: miss,
+            empty_poor: miss,
+        }
+    }
+
+    /// StepMania, judge 4. Marvelous, Perfect, Great, Good and Boo are mapped in order.
+    pub fn stepmania() -> HitWindows {
+        let boo = Window::symmetric(Ratio::new(180, 1000));
+        HitWindows {
+            pgreat: Window::symmetric(Ratio::new(45, 2000)),
+            great: Window::millis(45),
+            good: Window::millis(90),
+            bad: Window::millis(135),
+            poor: boo,
+            empty_poor: boo,
+        }
+    }
+
+    /// Returns grade for timing error, or `None` if out of hit windows.
+    pub fn grade(&self, error: Ratio<isize>) -> Option<Grade> {
+        [
+            (self.pgreat, Grade::PGreat),
+            (self.great, Grade::Great),
+            (self.good, Grade::Good),
+            (self.bad, Grade::Bad),
+            (self.poor, Grade::Poor),
+        ]
+        .into_iter()
+        .find(|(window, _)| window.contains(error))
+        .map(|(_, grade)| grade)
+    }
+}
+
+/// Represents a preset of hit windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowPreset {
+    Iidx,
+    Lr2,
+    Beatoraja,
+
+    /// osu!mania with overall difficulty.
+    OsuMania(Ratio<usize>),
+    StepMania,
+}
+
+impl WindowPreset {
+    pub fn windows(&self) -> HitWindows {
+        match *self {
+            WindowPreset::Iidx => HitWindows::iidx(),
+            WindowPreset::Lr2 => HitWindows::lr2(),
+            WindowPreset::Beatoraja => HitWindows::beatoraja(),
+            WindowPreset::OsuMania(od) => HitWindows::osu_mania(od),
+            WindowPreset::StepMania => HitWindows::stepmania(),
+        }
+    }
+}
+
+/// Represents input action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    Press,
+    Release,
+}
+
+/// Represents timestamped input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub lane: LaneId,
+    pub action: InputAction,
+
+    /// Playback seconds.
+    pub seconds: SignedSeconds,
+}
+
+/// Represents a kind of judgment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JudgmentKind {
+    /// The note is hit.
+    Hit(Grade),
+
+    /// The press is too early; the note remains.
+    EmptyPoor,
+
+    /// The note passed without hit.
+    Miss,
+
+    /// The hold note is released in the release window.
+    Release(Grade),
+
+    /// The hold note is released too early, held too long or its head is missed.
+    ReleaseMiss,
+
+    /// A tick of HCN, draining gauge unless held.
+    HoldTick { held: bool },
+}
+
+/// Represents a judgment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Judgment {
+    pub lane: LaneId,
+
+    /// The time of judged note, or of its release or tick.
+    pub time: Instant,
+    pub kind: JudgmentKind,
+
+    /// Playback seconds at which judged.
+    pub seconds: SignedSeconds,
+
+    /// Signed timing error, negative if early. `None` for misses and ticks.
+    pub error: Option<Ratio<isize>>,
+}
+
+/// Specifies how hold notes are judged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HoldMode {
+    /// Judges the head only.
+    Ln,
+
+    /// Judges the head and the release.
+    Cn,
+
+    /// Judges the head and the release, and ticks while holding.
+    /// Tick interval is in measures, e.g. 1/16.
+    Hcn { tick: Ratio<usize> },
+}
+
+/// Represents rules of hold notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoldRules {
+    pub mode: HoldMode,
+
+    /// Windows for the release. Releases out of `poor` are `JudgmentKind::ReleaseMiss`.
+    pub release: HitWindows,
+}
+
+/// Represents a judged note with its playback seconds.
+#[derive(Debug, Clone)]
+struct Target {
+    time: Instant,
+    seconds: Ratio<isize>,
+
+    /// The end of hold note, unless judged as LN.
+    end: Option<(Instant, Ratio<isize>)>,
+
+    /// Ticks of HCN.
+    ticks: Vec<(Instant, Ratio<isize>)>,
+}
+
+/// Represents a hold note whose head is judged.
+#[derive(Debug, Clone, Copy)]
+struct ActiveHold {
+    target: usize,
+    next_tick: usize,
+    released: bool,
+
+    /// Whether the head is missed; the release is not judged then.
+    broken: bool,
+}
+
+/// Judges input events against a chart.
+/// Events must be fed in order of seconds.
+#[derive(Debug, Clone)]
+pub struct Judge {
+    windows: HitWindows,
+    holds: HoldRules,
+    lanes: Vec<Vec<Target>>,
+
+    /// The index of the first pending note of each lane.
+    cursors: Vec<usize>,
+    pressed: Vec<bool>,
+    active: Vec<Option<ActiveHold>>,
+}
+
+impl Judge {
+    /// Creates new judge judging hold notes as LN. Only judged notes take part.
+    pub fn new(chart: &Chart, clock: &ChartClock, windows: HitWindows) -> Judge {
+        let holds = HoldRules {
+            mode: HoldMode::Ln,
+            release: windows,
+        };
+        Judge::with_holds(chart, clock, windows, holds)
+    }
+
+    /// Creates new judge with specified hold rules.
+    pub fn with_holds(
+        chart: &Chart,
+        clock: &ChartClock,
+        windows: HitWindows,
+        holds: HoldRules,
+    ) -> Judge {
+        let lanes: Vec<Vec<_>> = chart
+            .lanes()
+            .map(|(_, notes)| {
+                notes
+                    .pairs()
+                    .filter(|(_, note)| note.is_judged())
+                    .map(|(time, note)| {
+                        let end = note
+                            .hold_end()
+                            .filter(|_| holds.mode != HoldMode::Ln)
+                            .map(|end| (end, clock.judgment_seconds_at(end)));
+                        let ticks = match (holds.mode, end) {
+                            (HoldMode::Hcn { tick }, Some((end, _))) => hold_ticks(time, end, tick)
+                                .map(|t| (t, clock.judgment_seconds_at(t)))
+                                .collect(),
+                            _ => vec![],
+                        };
+                        Target {
+                            time,
+                            seconds: clock.judgment_seconds_at(time),
+                            end,
+                            ticks,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Judge {
+            windows,
+            holds,
+            cursors: vec![0; lanes.len()],
+            pressed: vec![false; lanes.len()],
+            active: vec![None; lanes.len()],
+            lanes,
+        }
+    }
+
+    pub fn windows(&self) -> &HitWindows {
+        &self.windows
+    }
+
+    pub fn holds(&self) -> &HoldRules {
+        &self.holds
+    }
+
+    /// Returns the number of judgments of notes and releases, excluding ticks.
+    pub fn note_count(&self) -> usize {
+        self.lanes
+            .iter()
+            .flatten()
+            .map(|target| 1 + usize::from(target.end.is_some()))
+            .sum()
+    }
+
+    /// Returns whether all notes are judged.
+    pub fn is_finished(&self) -> bool {
+        self.active.iter().all(Option::is_none)
+            && self
+                .lanes
+                .iter()
+                .zip(self.cursors.iter())
+                .all(|(targets, &cursor)| cursor >= targets.len())
+    }
+
+    /// Feeds an input event, pushing resulting judgments.
+    pub fn input(&mut self, event: InputEvent, judgments: &mut Vec<Judgment>) {
+        self.advance(event.seconds, judgments);
+        let SignedSeconds(seconds) = event.seconds;
+
+        let LaneId(lane) = event.lane;
+        if lane >= self.lanes.len() {
+            return;
+        }
+        self.pressed[lane] = event.action == InputAction::Press;
+        match event.action {
+            InputAction::Press => self.press(lane, seconds, judgments),
+            InputAction::Release => self.release(lane, seconds, judgments),
+        }
+    }
+
+    fn press(&mut self, lane: usize, seconds: Ratio<isize>, judgments: &mut Vec<Judgment>) {
+        // presses during hold are not judged
+        if self.active[lane].is_some_and(|a| !a.released && !a.broken) {
+            return;
+        }
+        let cursor = self.cursors[lane];
+        let Some(target) = self.lanes[lane].get(cursor) else {
+            return;
+        };
+        let (time, end) = (target.time, target.end);
+
+        let error = seconds - target.seconds;
+        let kind = match self.windows.grade(error) {
+            Some(grade) => {
+                self.cursors[lane] += 1;
+                self.drop_hold(lane, judgments);
+                if end.is_some() {
+                    self.active[lane] = Some(ActiveHold {
+                        target: cursor,
+                        next_tick: 0,
+                        released: false,
+                        broken: false,
+                    });
+                }
+                JudgmentKind::Hit(grade)
+            }
+            None if self.windows.empty_poor.contains(error) => JudgmentKind::EmptyPoor,
+            None => return,
+        };
+        judgments.push(Judgment {
+            lane: LaneId(lane),
+            time,
+            kind,
+            seconds: SignedSeconds(seconds),
+            error: Some(error),
+        });
+    }
+
+    fn release(&mut self, lane: usize, seconds: Ratio<isize>, judgments: &mut Vec<Judgment>) {
+        let Some(active) = self.active[lane]
+            .as_mut()
+            .filter(|a| !a.released && !a.broken)
+        else {
+            return;
+        };
+        let (end, end_seconds) = self.lanes[lane][active.target].end.expect("must be hold");
+
+        let error = seconds - end_seconds;
+        let (kind, error) = match self.holds.release.grade(error) {
+            Some(grade) => (JudgmentKind::Release(grade), Some(error)),
+            None => (JudgmentKind::ReleaseMiss, None),
+        };
+        active.released = true;
+        judgments.push(Judgment {
+            lane: LaneId(lane),
+            time: end,
+            kind,
+            seconds: SignedSeconds(seconds),
+            error,
+        });
+        self.retire(lane, seconds);
+    }
+
+    /// Advances to specified seconds, pushing misses, overdue releases and ticks in order of seconds.
+    pub fn advance(&mut self, seconds: SignedSeconds, judgments: &mut Vec<Judgment>) {
+        let SignedSeconds(seconds) = seconds;
+        let first = judgments.len();
+        let late = signed_ratio(self.windows.poor.late);
+        let release_late = signed_ratio(self.holds.release.poor.late);
+        for (lane, targets) in self.lanes.iter().enumerate() {
+            let cursor = &mut self.cursors[lane];
+            while let Some(target) = targets.get(*cursor) {
+                let deadline = target.seconds + late;
+                if deadline >= seconds {
+                    break;
+                }
+                judgments.push(Judgment {
+                    lane: LaneId(lane),
+                    time: target.time,
+                    kind: JudgmentKind::Miss,
+                    seconds: SignedSeconds(deadline),
+                    error: None,
+                });
+                if target.end.is_some() {
+                    // broken hold keeps ticking until its end
+                    self.active[lane] = Some(ActiveHold {
+                        target: *cursor,
+                        next_tick: 0,
+                        released: false,
+                        broken: true,
+                    });
+                }
+                *cursor += 1;
+            }
+
+            let Some(active) = self.active[lane].as_mut() else {
+                continue;
+            };
+            let target = &targets[active.target];
+            while let Some(&(time, tick_seconds)) = target.ticks.get(active.next_tick) {
+                if tick_seconds >= seconds {
+                    break;
+                }
+                judgments.push(Judgment {
+                    lane: LaneId(lane),
+                    time,
+                    kind: JudgmentKind::HoldTick {
+                        held: self.pressed[lane],
+                    },
+                    seconds: SignedSeconds(tick_seconds),
+                    error: None,
+                });
+                active.next_tick += 1;
+            }
+
+            let (end, end_seconds) = target.end.expect("must be hold");
+            // broken hold is never released by input, so it ends at once
+            let deadline = if active.broken {
+                end_seconds
+            } else {
+                end_seconds + release_late
+            };
+            if !active.released && deadline < seconds {
+                active.released = true;
+                judgments.push(Judgment {
+                    lane: LaneId(lane),
+                    time: end,
+                    kind: JudgmentKind::ReleaseMiss,
+                    seconds: SignedSeconds(deadline),
+                    error: None,
+                });
+            }
+        }
+        for lane in 0..self.lanes.len() {
+            self.retire(lane, seconds);
+        }
+        judgments[first..].sort_by_key(|j| j.seconds);
+    }
+
+    /// Judges all remaining notes as missed.
+    pub fn finish(&mut self, judgments: &mut Vec<Judgment>) {
+        let last = self
+            .lanes
+            .iter()
+            .flatten()
+            .map(|target| target.end.map_or(target.seconds, |(_, s)| s))
+            .max();
+        if let Some(last) = last {
+            let late = self.windows.poor.late.max(self.holds.release.poor.late);
+            self.advance(SignedSeconds(last + signed_ratio(late) + 1), judgments);
+        }
+    }
+
+    /// Judges all input events at once, including misses after the last event.
+    pub fn run(mut self, events: impl IntoIterator<Item = InputEvent>) -> Vec<Judgment> {
+        let mut judgments = vec![];
+        for event in events {
+            self.input(event, &mut judgments);
+        }
+        self.finish(&mut judgments);
+        judgments
+    }
+
+    /// Ends active hold of the lane at once, judging its remaining ticks as not held.
+    fn drop_hold(&mut self, lane: usize, judgments: &mut Vec<Judgment>) {
+        let Some(active) = self.active[lane].take() else {
+            return;
+        };
+        let target = &self.lanes[lane][active.target];
+        for &(time, tick_seconds) in &target.ticks[active.next_tick..] {
+            judgments.push(Judgment {
+                lane: LaneId(lane),
+                time,
+                kind: JudgmentKind::HoldTick { held: false },
+                seconds: SignedSeconds(tick_seconds),
+                error: None,
+            });
+        }
+        if !active.released {
+            let (end, end_seconds) = target.end.expect("must be hold");
+            judgments.push(Judgment {
+                lane: LaneId(lane),
+                time: end,
+                kind: JudgmentKind::ReleaseMiss,
+                seconds: SignedSeconds(end_seconds),
+                error: None,
+            });
+        }
+    }
+
+    /// Clears active hold if released and all ticks are passed.
+    fn retire(&mut self, lane: usize, seconds: Ratio<isize>) {
+        let Some(active) = self.active[lane] else {
+            return;
+        };
+        let target = &self.lanes[lane][active.target];
+        let ticking = target
+            .ticks
+            .get(active.next_tick)
+            .is_some_and(|&(_, s)| s >= seconds);
+        if active.released && !ticking {
+            self.active[lane] = None;
+        }
+    }
+}
+
+/// Returns instants of HCN ticks, exclusive of both ends.
+fn hold_ticks(start: Instant, end: Instant, tick: Ratio<usize>) -> impl Iterator<Item = Instant> {
+    assert!(tick > Ratio::from_integer(0), "invalid tick interval");
+    (1..)
+        .map(move |k| Instant::from_measures(start.to_measures() + tick * k))
+        .take_while(move |&t| t < end)
+}
+
+/// Judges all input events with [`Judge::new`]; see [`Judge::run`].
+pub fn judge_all(
+    chart: &Chart,
+    clock: &ChartClock,
+    windows: HitWindows,
+    events: impl IntoIterator<Item = InputEvent>,
+) -> Vec<Judgment> {
+    Judge::new(chart, clock, windows).run(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        judge_all, Grade, HitWindows, HoldMode, HoldRules, InputAction, InputEvent, Judge,
+        JudgmentKind, Window,
+    };
+    use crate::{
+        fixture::{self, hold},
+        instant,
+        note::{LaneId, NoteKind},
+        time::SignedSeconds,
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn window_presets_work() {
+        let iidx = HitWindows::iidx();
+        assert_eq!(iidx.grade(Ratio::new(-1, 100)), Some(Grade::PGreat));
+        assert_eq!(iidx.grade(Ratio::new(3, 100)), Some(Grade::Great));
+        assert_eq!(iidx.grade(Ratio::new(-1, 3)), None);
+
+        let beatoraja = HitWindows::beatoraja();
+        assert_eq!(beatoraja.grade(Ratio::new(-1, 4)), Some(Grade::Bad));
+        assert_eq!(beatoraja.grade(Ratio::new(1, 4)), None, "asymmetric");
+
+        let osu = HitWindows::osu_mania(Ratio::from_integer(8));
+        assert_eq!(osu.great, Window::millis(40));
+        assert_eq!(osu.grade(Ratio::new(150, 1000)), Some(Grade::Poor));
+        assert_eq!(osu.grade(Ratio::new(165, 1000)), None);
+
+        for windows in [HitWindows::lr2(), HitWindows::stepmania()] {
+            assert!(windows.pgreat.late < windows.great.late);
+            assert!(windows.bad.late <= windows.poor.late);
+        }
+    }
+
+    #[test]
+    fn judge_works() {
+        let (chart, clock) = fixture::chart(
+            2,
+            &[
+                (instant![1:0/1], 0, NoteKind::Tap),
+                (instant![1:1/2], 0, NoteKind::Tap),
+                (instant![1:1/2], 1, NoteKind::Mine),
+                (instant![2:0/1], 1, NoteKind::Tap),
+            ],
+        );
+
+        let press = |lane, seconds| InputEvent {
+            lane: LaneId(lane),
+            action: InputAction::Press,
+            seconds: SignedSeconds(seconds),
+        };
+        let judgments = judge_all(
+            &chart,
+            &clock,
+            HitWindows::iidx(),
+            [
+                press(0, Ratio::new(16, 10)),
+                press(0, Ratio::new(199, 100)),
+                press(1, Ratio::new(4, 1)),
+            ],
+        );
+        let kinds: Vec<_> = judgments.iter().map(|j| (j.time, j.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (instant![1:0/1], JudgmentKind::EmptyPoor),
+                (instant![1:0/1], JudgmentKind::Hit(Grade::PGreat)),
+                (instant![1:1/2], JudgmentKind::Miss),
+                (instant![2:0/1], JudgmentKind::Hit(Grade::PGreat)),
+            ]
+        );
+        assert_eq!(judgments[1].error, Some(Ratio::new(-1, 100)));
+        assert_eq!(judgments[2].seconds, SignedSeconds(Ratio::new(13, 4)));
+    }
+
+    #[test]
+    fn judge_edges_work() {
+        let (empty, clock) = fixture::chart(2, &[]);
+        let judge = Judge::new(&empty, &clock, HitWindows::iidx());
+        assert!(judge.is_finished());
+        assert_eq!(judge.note_count(), 0);
+        assert!(judge_all(&empty, &clock, HitWindows::iidx(), []).is_empty());
+
+        // the only note is at 2 seconds, the end of the chart
+        let (single, clock) = fixture::chart(1, &[(instant![1:0/1], 0, NoteKind::Tap)]);
+        let press = |lane, seconds| InputEvent {
+            lane: LaneId(lane),
+            action: InputAction::Press,
+            seconds: SignedSeconds(seconds),
+        };
+        let judge = |events: &[InputEvent]| {
+            let judgments = judge_all(&single, &clock, HitWindows::iidx(), events.iter().copied());
+            judgments.iter().map(|j| j.kind).collect::<Vec<_>>()
+        };
+        assert_eq!(judge(&[]), vec![JudgmentKind::Miss], "missed at finish");
+        assert_eq!(
+            judge(&[press(0, Ratio::new(9, 4))]),
+            vec![JudgmentKind::Hit(Grade::Bad)],
+            "the last moment of the window"
+        );
+        assert_eq!(
+            judge(&[press(0, Ratio::new(226, 100))]),
+            vec![JudgmentKind::Miss],
+            "no note is left after the window"
+        );
+        assert_eq!(
+            judge(&[press(1, Ratio::from_integer(2))]),
+            vec![JudgmentKind::Miss],
+            "lane out of range"
+        );
+    }
+
+    #[test]
+    fn hold_modes_work() {
+        let (chart, clock) = fixture::chart(
+            1,
+            &[
+                (instant![1:0/1], 0, hold(instant![2:0/1])),
+                (instant![3:0/1], 0, hold(instant![4:0/1])),
+            ],
+        );
+        let event = |action, seconds| InputEvent {
+            lane: LaneId(0),
+            action,
+            seconds: SignedSeconds(seconds),
+        };
+        let rules = |mode| HoldRules {
+            mode,
+            release: HitWindows::iidx(),
+        };
+
+        let judge = Judge::with_holds(&chart, &clock, HitWindows::iidx(), rules(HoldMode::Cn));
+        assert_eq!(judge.note_count(), 4);
+        let judgments = judge.run([
+            event(InputAction::Press, Ratio::new(201, 100)),
+            event(InputAction::Release, Ratio::new(403, 100)),
+        ]);
+        let kinds: Vec<_> = judgments.iter().map(|j| (j.time, j.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (instant![1:0/1], JudgmentKind::Hit(Grade::PGreat)),
+                (instant![2:0/1], JudgmentKind::Release(Grade::Great)),
+                (instant![3:0/1], JudgmentKind::Miss),
+                (instant![4:0/1], JudgmentKind::ReleaseMiss),
+            ]
+        );
+        assert_eq!(
+            judgments[1].error,
+            Some(Ratio::new(3, 100)),
+            "graded release"
+        );
+
+        let hcn = rules(HoldMode::Hcn {
+            tick: Ratio::new(1, 2),
+        });
+        let judgments = Judge::with_holds(&chart, &clock, HitWindows::iidx(), hcn).run([
+            event(InputAction::Press, Ratio::new(2, 1)),
+            event(InputAction::Release, Ratio::new(11, 4)),
+            event(InputAction::Press, Ratio::new(29, 10)),
+            event(InputAction::Release, Ratio::new(39, 10)),
+        ]);
+        let kinds: Vec<_> = judgments.iter().map(|j| (j.time, j.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (instant![1:0/1], JudgmentKind::Hit(Grade::PGreat)),
+                (instant![2:0/1], JudgmentKind::ReleaseMiss),
+                (instant![1:1/2], JudgmentKind::HoldTick { held: true }),
+                (instant![3:0/1], JudgmentKind::Miss),
+                (instant![3:1/2], JudgmentKind::HoldTick { held: false }),
+                (instant![4:0/1], JudgmentKind::ReleaseMiss),
+            ]
+        );
+        assert_eq!(judgments[1].error, None, "release out of windows is a miss");
+        assert_eq!(judgments[1].seconds, SignedSeconds(Ratio::new(11, 4)));
+    }
+
+    #[test]
+    fn missed_hold_does_not_block_next_note() {
+        let (chart, clock) = fixture::chart(
+            1,
+            &[
+                (instant![1:0/1], 0, hold(instant![2:0/1])),
+                (instant![2:1/16], 0, NoteKind::Tap),
+            ],
+        );
+        let press = |seconds| InputEvent {
+            lane: LaneId(0),
+            action: InputAction::Press,
+            seconds: SignedSeconds(seconds),
+        };
+        let cn = HoldRules {
+            mode: HoldMode::Cn,
+            release: HitWindows::iidx(),
+        };
+
+        // the tap follows the end of the missed hold within the release window
+        let judgments = Judge::with_holds(&chart, &clock, HitWindows::iidx(), cn)
+            .run([press(Ratio::new(33, 8))]);
+        let kinds: Vec<_> = judgments.iter().map(|j| (j.time, j.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (instant![1:0/1], JudgmentKind::Miss),
+                (instant![2:0/1], JudgmentKind::ReleaseMiss),
+                (instant![2:1/16], JudgmentKind::Hit(Grade::PGreat)),
+            ]
+        );
+
+        // the tap is hit early, before the missed hold ends
+        let judgments = Judge::with_holds(&chart, &clock, HitWindows::iidx(), cn)
+            .run([press(Ratio::new(79, 20))]);
+        let kinds: Vec<_> = judgments.iter().map(|j| (j.time, j.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (instant![1:0/1], JudgmentKind::Miss),
+                (instant![2:0/1], JudgmentKind::ReleaseMiss),
+                (instant![2:1/16], JudgmentKind::Hit(Grade::Bad)),
+            ]
+        );
+    }
+}
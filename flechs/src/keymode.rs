@@ -0,0 +1,386 @@
+//! Contains key-mode descriptors and conversion between modes.
+
+use crate::{
+    chart::Chart,
+    note::{LaneId, LaneKind, Note},
+    time::Instant,
+};
+
+use thiserror::Error as ThisError;
+
+use self::{
+    LaneRole::{Key, Scratch},
+    Side::{P1, P2},
+};
+
+/// Represents an error about key mode.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum KeyModeError {
+    /// Chart has different number of lanes from the mode.
+    #[error("expected {expected} lanes, found {found}")]
+    LaneCountMismatch { expected: usize, found: usize },
+}
+
+/// Represents a side of play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Side {
+    P1,
+    P2,
+}
+
+/// Represents a role of lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaneRole {
+    /// Key of the side, numbered from 1.
+    Key(Side, usize),
+    Scratch(Side),
+}
+
+/// Represents a key mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyMode {
+    K4,
+
+    /// 5 keys and scratch.
+    K5,
+
+    /// 7 keys and scratch.
+    K7,
+
+    /// Pop'n Music 9 keys.
+    K9,
+
+    /// Double play of 5 keys and scratch.
+    K10,
+
+    /// Double play of 7 keys and scratch.
+    K14,
+
+    /// 24 keys and 2 scratches (wheels).
+    K24,
+}
+
+const K4: [LaneRole; 4] = [Key(P1, 1), Key(P1, 2), Key(P1, 3), Key(P1, 4)];
+const K5: [LaneRole; 6] = [
+    Scratch(P1),
+    Key(P1, 1),
+    Key(P1, 2),
+    Key(P1, 3),
+    Key(P1, 4),
+    Key(P1, 5),
+];
+const K7: [LaneRole; 8] = [
+    Scratch(P1),
+    Key(P1, 1),
+    Key(P1, 2),
+    Key(P1, 3),
+    Key(P1, 4),
+    Key(P1, 5),
+    Key(P1, 6),
+    Key(P1, 7),
+];
+const K9: [LaneRole; 9] = [
+    Key(P1, 1),
+    Key(P1, 2),
+    Key(P1, 3),
+    Key(P1, 4),
+    Key(P1, 5),
+    Key(P1, 6),
+    Key(P1, 7),
+    Key(P1, 8),
+    Key(P1, 9),
+];
+const K10: [LaneRole; 12] = [
+    Scratch(P1),
+    Key(P1, 1),
+    Key(P1, 2),
+    Key(P1, 3),
+    Key(P1, 4),
+    Key(P1, 5),
+    Key(P2, 1),
+    Key(P2, 2),
+    Key(P2, 3),
+    Key(P2, 4),
+    Key(P2, 5),
+    Scratch(P2),
+];
+const K14: [LaneRole; 16] = [
+    Scratch(P1),
+    Key(P1, 1),
+    Key(P1, 2),
+    Key(P1, 3),
+    Key(P1, 4),
+    Key(P1, 5),
+    Key(P1, 6),
+    Key(P1, 7),
+    Key(P2, 1),
+    Key(P2, 2),
+    Key(P2, 3),
+    Key(P2, 4),
+    Key(P2, 5),
+    Key(P2, 6),
+    Key(P2, 7),
+    Scratch(P2),
+];
+const K24: [LaneRole; 26] = {
+    let mut lanes = [Scratch(P1); 26];
+    let mut i = 0;
+    while i < 24 {
+        lanes[i] = Key(P1, i + 1);
+        i += 1;
+    }
+    lanes[24] = Scratch(P1);
+    lanes[25] = Scratch(P2);
+    lanes
+};
+
+/// BMS channels of 1P keys 1 to 7; 2P channels are 10 more.
+const BMS_KEYS: [u8; 7] = [11, 12, 13, 14, 15, 18, 19];
+
+/// BMS channel of 1P scratch.
+const BMS_SCRATCH: u8 = 16;
+
+/// PMS channels of keys 1 to 9.
+const PMS_KEYS: [u8; 9] = [11, 12, 13, 14, 15, 22, 23, 24, 25];
+
+impl KeyMode {
+    /// Returns roles of lanes, left to right.
+    pub const fn layout(&self) -> &'static [LaneRole] {
+        match self {
+            KeyMode::K4 => &K4,
+            KeyMode::K5 => &K5,
+            KeyMode::K7 => &K7,
+            KeyMode::K9 => &K9,
+            KeyMode::K10 => &K10,
+            KeyMode::K14 => &K14,
+            KeyMode::K24 => &K24,
+        }
+    }
+
+    pub const fn lane_count(&self) -> usize {
+        self.layout().len()
+    }
+
+    /// Returns kinds of lanes, left to right.
+    pub fn lane_kinds(&self) -> Vec<LaneKind> {
+        self.layout()
+            .iter()
+            .map(|role| match role {
+                Key(..) => LaneKind::Key,
+                Scratch(_) => LaneKind::Scratch,
+            })
+            .collect()
+    }
+
+    /// Returns whether the mode is double play.
+    pub const fn is_double(&self) -> bool {
+        matches!(self, KeyMode::K10 | KeyMode::K14)
+    }
+
+    /// Returns the role of lane.
+    pub fn role(&self, lane: LaneId) -> Option<LaneRole> {
+        self.layout().get(lane.0).copied()
+    }
+
+    /// Returns key lanes, left to right.
+    pub fn keys(&self) -> Vec<LaneId> {
+        self.lanes_where(|role| matches!(role, Key(..)))
+    }
+
+    /// Returns key lanes of the side, left to right.
+    pub fn side_keys(&self, side: Side) -> Vec<LaneId> {
+        self.lanes_where(|role| matches!(role, Key(s, _) if s == side))
+    }
+
+    /// Returns scratch lanes, left to right.
+    pub fn scratches(&self) -> Vec<LaneId> {
+        self.lanes_where(|role| matches!(role, Scratch(_)))
+    }
+
+    /// Returns BMS channel (PMS for 9K) of the lane, or `None` if it has no standard channel.
+    pub fn bms_channel(&self, lane: LaneId) -> Option<u8> {
+        let offset = |side| if side == P2 { 10 } else { 0 };
+        match (self, self.role(lane)?) {
+            (KeyMode::K9, Key(_, key)) => Some(PMS_KEYS[key - 1]),
+            (KeyMode::K24, _) => None,
+            // 4K is placed on keys 1 to 4
+            (_, Key(side, key)) => Some(BMS_KEYS[key - 1] + offset(side)),
+            (_, Scratch(side)) => Some(BMS_SCRATCH + offset(side)),
+        }
+    }
+
+    fn lanes_where(&self, f: impl Fn(LaneRole) -> bool) -> Vec<LaneId> {
+        (0..self.lane_count())
+            .map(LaneId)
+            .filter(|&lane| self.role(lane).is_some_and(&f))
+            .collect()
+    }
+}
+
+/// Represents the result of conversion.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub chart: Chart,
+
+    /// The number of notes dropped by collision or lack of scratch.
+    pub dropped: usize,
+}
+
+/// Converts chart between key modes. Keys are mapped in proportion to their positions,
+/// and scratches likewise onto scratches; scratch notes are dropped if the mode has none.
+/// Notes landing on the same time or on a hold of another note are dropped;
+/// a hold occupies its lane over `[start, end)`, as in lane transforms.
+pub fn convert(chart: &Chart, from: KeyMode, to: KeyMode) -> Result<Conversion, KeyModeError> {
+    if chart.lane_count() != from.lane_count() {
+        return Err(KeyModeError::LaneCountMismatch {
+            expected: from.lane_count(),
+            found: chart.lane_count(),
+        });
+    }
+
+    let map = |sources: Vec<LaneId>, targets: Vec<LaneId>| -> Vec<(LaneId, Option<LaneId>)> {
+        let (n, m) = (sources.len(), targets.len());
+        sources
+            .into_iter()
+            .enumerate()
+            .map(|(i, lane)| (lane, (m > 0).then(|| targets[(2 * i + 1) * m / (2 * n)])))
+            .collect()
+    };
+    let mut lane_map = map(from.keys(), to.keys());
+    lane_map.extend(map(from.scratches(), to.scratches()));
+
+    let mut notes: Vec<_> = chart.notes().map(|(time, note)| (time, *note)).collect();
+    notes.sort_by_key(|(time, _)| *time);
+
+    let mut result = Chart::with_lanes(chart.rhythm().clone(), to.lane_kinds());
+    let mut hold_ends: Vec<Option<Instant>> = vec![None; to.lane_count()];
+    let mut dropped = 0;
+    for (time, note) in notes {
+        let target = lane_map
+            .iter()
+            .find(|(source, _)| *source == note.lane)
+            .and_then(|(_, target)| *target);
+        let Some(lane) = target.filter(|lane| hold_ends[lane.0].is_none_or(|end| end <= time))
+        else {
+            dropped += 1;
+            continue;
+        };
+
+        if result.insert_note(time, Note { lane, ..note }).is_err() {
+            dropped += 1;
+            continue;
+        }
+        hold_ends[lane.0] = note.hold_end();
+    }
+    for (time, keysound) in chart.bgm().pairs() {
+        result.insert_bgm(time, *keysound);
+    }
+
+    Ok(Conversion {
+        chart: result,
+        dropped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, KeyMode, KeyModeError, LaneRole, Side};
+    use crate::{
+        chart::Chart,
+        fixture::{self, hold},
+        instant,
+        note::{LaneId, LaneKind, NoteKind},
+    };
+
+    /// Returns chart of the mode with notes at the start of measure 1.
+    fn chart(mode: KeyMode, notes: &[(usize, NoteKind)]) -> Chart {
+        let notes: Vec<_> = notes
+            .iter()
+            .map(|&(lane, kind)| (instant![1:0/1], lane, kind))
+            .collect();
+        fixture::chart_with(fixture::rhythm(120), mode.lane_kinds(), &notes).0
+    }
+
+    #[test]
+    fn key_mode_layout_works() {
+        assert_eq!(KeyMode::K7.lane_count(), 8);
+        assert_eq!(KeyMode::K14.lane_count(), 16);
+        assert_eq!(KeyMode::K24.lane_count(), 26);
+        assert_eq!(KeyMode::K24.keys().len(), 24);
+        assert_eq!(KeyMode::K14.scratches(), vec![LaneId(0), LaneId(15)]);
+        assert_eq!(
+            KeyMode::K10.side_keys(Side::P2),
+            (6..11).map(LaneId).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            KeyMode::K5.role(LaneId(0)),
+            Some(LaneRole::Scratch(Side::P1))
+        );
+
+        assert_eq!(KeyMode::K7.bms_channel(LaneId(0)), Some(16));
+        assert_eq!(KeyMode::K7.bms_channel(LaneId(7)), Some(19));
+        assert_eq!(KeyMode::K14.bms_channel(LaneId(8)), Some(21));
+        assert_eq!(KeyMode::K14.bms_channel(LaneId(15)), Some(26));
+        assert_eq!(KeyMode::K9.bms_channel(LaneId(8)), Some(25));
+        assert_eq!(KeyMode::K24.bms_channel(LaneId(0)), None);
+    }
+
+    #[test]
+    fn key_mode_conversion_works() {
+        // 7K+S chord; keys 1 and 2 collide onto 4K key 1
+        let seven = chart(
+            KeyMode::K7,
+            &[
+                (0, NoteKind::Tap),
+                (1, NoteKind::Tap),
+                (2, NoteKind::Tap),
+                (
+                    4,
+                    NoteKind::Hold {
+                        end: instant![2:0/1],
+                    },
+                ),
+            ],
+        );
+        let four = convert(&seven, KeyMode::K7, KeyMode::K4).unwrap();
+        assert_eq!(four.dropped, 2, "a collision and a scratch");
+        let lanes: Vec<_> = four.chart.notes().map(|(_, n)| n.lane.0).collect();
+        assert_eq!(lanes, vec![0, 2]);
+
+        let five = chart(
+            KeyMode::K5,
+            &[(0, NoteKind::Tap), (1, NoteKind::Tap), (5, NoteKind::Tap)],
+        );
+        let seven = convert(&five, KeyMode::K5, KeyMode::K7).unwrap();
+        assert_eq!(seven.dropped, 0);
+        let lanes: Vec<_> = seven.chart.notes().map(|(_, n)| n.lane.0).collect();
+        assert_eq!(lanes, vec![0, 1, 7]);
+        assert_eq!(seven.chart.lane_kind(LaneId(0)), Some(LaneKind::Scratch));
+
+        assert_eq!(
+            convert(&five, KeyMode::K7, KeyMode::K4).unwrap_err(),
+            KeyModeError::LaneCountMismatch {
+                expected: 8,
+                found: 6
+            }
+        );
+    }
+
+    #[test]
+    fn key_mode_conversion_frees_lane_at_hold_end() {
+        // keys 1 and 2 collide onto 4K key 1; the tap lands right at the end of the hold
+        let mut seven = chart(KeyMode::K7, &[(1, hold(instant![2:0/1]))]);
+        fixture::insert_notes(
+            &mut seven,
+            &[
+                (instant![2:0/1], 2, NoteKind::Tap),
+                (instant![1:1/2], 2, NoteKind::Tap),
+            ],
+        );
+
+        let four = convert(&seven, KeyMode::K7, KeyMode::K4).unwrap();
+        assert_eq!(four.dropped, 1, "only the tap during the hold");
+        let notes: Vec<_> = four.chart.notes().map(|(t, n)| (t, n.lane.0)).collect();
+        assert_eq!(notes, vec![(instant![1:0/1], 0), (instant![2:0/1], 0)]);
+    }
+}
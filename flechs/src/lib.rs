@@ -1,7 +1,30 @@
 //! Flexible high-speed manipulation library for rhythm games.
 
+pub mod analysis;
+pub mod autoplay;
+pub mod bpm;
+pub mod chart;
+pub mod clock;
+pub mod difficulty;
+#[cfg(test)]
+mod fixture;
+pub mod gauge;
+pub mod gridline;
+pub mod hispeed;
+pub mod judge;
+pub mod keymode;
+pub mod note;
+pub mod pattern;
 pub mod preintegral;
+pub mod render;
+pub mod replay;
+pub mod score;
+pub mod snap;
+pub mod span;
+pub mod tick;
 pub mod time;
 pub mod timeline;
+pub mod transform;
 pub mod util;
 pub mod value;
+pub mod visibility;
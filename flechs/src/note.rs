@@ -0,0 +1,130 @@
+//! Contains note types.
+
+use crate::time::Instant;
+
+/// Identifies a lane in chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LaneId(pub usize);
+
+/// Identifies a keysound (BMS `#WAVxx`, bmson sound channel, etc.).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeysoundId(pub usize);
+
+/// Represents a kind of note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoteKind {
+    /// Normal note, judged on press.
+    Tap,
+
+    /// Long note. Starts at the time stored in timeline and ends at `end`.
+    Hold { end: Instant },
+
+    /// Damages on press, never counted as a hit.
+    Mine,
+
+    /// Not rendered, plays its keysound when pressed.
+    Invisible,
+
+    /// Rendered but never judged.
+    Fake,
+}
+
+/// Represents a kind of lane, on which the same notes are played differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum LaneKind {
+    /// Button or key.
+    #[default]
+    Key,
+
+    /// Turntable.
+    Scratch,
+}
+
+/// Represents a note event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Note {
+    /// Lane which this note belongs to.
+    pub lane: LaneId,
+
+    /// Keysound played by this note.
+    pub keysound: Option<KeysoundId>,
+
+    /// Kind of this note.
+    pub kind: NoteKind,
+}
+
+impl Note {
+    /// Creates new note.
+    pub const fn new(lane: LaneId, kind: NoteKind) -> Note {
+        Note {
+            lane,
+            keysound: None,
+            kind,
+        }
+    }
+
+    /// Sets keysound.
+    pub const fn with_keysound(self, keysound: KeysoundId) -> Note {
+        Note {
+            keysound: Some(keysound),
+            ..self
+        }
+    }
+
+    /// Returns end time if this is a hold note.
+    pub const fn hold_end(&self) -> Option<Instant> {
+        match self.kind {
+            NoteKind::Hold { end } => Some(end),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this note is judged.
+    pub const fn is_judged(&self) -> bool {
+        matches!(self.kind, NoteKind::Tap | NoteKind::Hold { .. })
+    }
+
+    /// Returns whether this note is rendered.
+    pub const fn is_rendered(&self) -> bool {
+        !matches!(self.kind, NoteKind::Invisible)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeysoundId, LaneId, Note, NoteKind};
+    use crate::instant;
+
+    #[test]
+    fn note_accessors_work() {
+        let tap = Note::new(LaneId(1), NoteKind::Tap).with_keysound(KeysoundId(3));
+        assert_eq!(tap.keysound, Some(KeysoundId(3)), "keysound is set");
+        assert_eq!(tap.hold_end(), None, "tap has no end");
+        assert!(tap.is_judged(), "tap is judged");
+
+        let hold = Note::new(
+            LaneId(0),
+            NoteKind::Hold {
+                end: instant![2:1/2],
+            },
+        );
+        assert_eq!(hold.hold_end(), Some(instant![2:1/2]), "hold has end");
+
+        assert!(
+            !Note::new(LaneId(0), NoteKind::Fake).is_judged(),
+            "fake is not judged"
+        );
+        assert!(
+            !Note::new(LaneId(0), NoteKind::Mine).is_judged(),
+            "mine is not judged"
+        );
+        assert!(
+            !Note::new(LaneId(0), NoteKind::Invisible).is_rendered(),
+            "invisible is not rendered"
+        );
+        assert!(
+            Note::new(LaneId(0), NoteKind::Fake).is_rendered(),
+            "fake is rendered"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Contains pattern recognition over note rows.
+//!
+//! Judged notes at the same time form a row. Patterns are maximal runs of rows,
+//! spanning from the first row of the run to where another row of the run would come:
+//! one interval of the last two rows after the last row, or the next row if it comes earlier.
+
+use crate::{chart::Chart, clock::ChartClock, note::LaneId, span::SpanTimeline, time::Instant};
+
+use std::ops::Range;
+
+use num::rational::Ratio;
+
+/// The minimum number of rows (or hold notes) forming a pattern.
+pub const MIN_PATTERN_ROWS: usize = 4;
+
+/// Represents a kind of pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternKind {
+    /// Single notes repeating on a lane.
+    Jack,
+
+    /// Single notes alternating between two lanes.
+    Trill,
+
+    /// Single notes sweeping lanes in one direction.
+    Roll,
+
+    /// Stream of single notes and chords, without repeating a lane in consecutive rows.
+    Jumpstream,
+
+    /// Chords each sharing a lane with the previous one.
+    Chordjack,
+
+    /// Overlapping hold notes.
+    LongNote,
+}
+
+/// Represents a recognized pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+
+    /// The number of judged notes in the pattern.
+    pub notes: usize,
+
+    /// Notes per second from the first row to the last row, excluding the last row.
+    pub intensity: Ratio<usize>,
+}
+
+/// Represents judged notes at the same time.
+#[derive(Debug, Clone)]
+struct Row {
+    time: Instant,
+    seconds: Ratio<usize>,
+
+    /// Lanes in ascending order.
+    lanes: Vec<LaneId>,
+}
+
+impl Row {
+    fn single(&self) -> Option<LaneId> {
+        match self.lanes.as_slice() {
+            [lane] => Some(*lane),
+            _ => None,
+        }
+    }
+
+    fn shares_lane(&self, other: &Row) -> bool {
+        self.lanes.iter().any(|lane| other.lanes.contains(lane))
+    }
+}
+
+/// Recognizes all patterns of the chart. Patterns of different kinds may overlap.
+pub fn recognize(chart: &Chart, clock: &ChartClock) -> SpanTimeline<Instant, Pattern> {
+    let mut notes: Vec<_> = chart
+        .notes()
+        .filter(|(_, note)| note.is_judged())
+        .map(|(time, note)| (time, note.lane))
+        .collect();
+    notes.sort();
+    let rows: Vec<_> = notes
+        .chunk_by(|a, b| a.0 == b.0)
+        .map(|chunk| Row {
+            time: chunk[0].0,
+            seconds: clock.seconds_at(chunk[0].0),
+            lanes: chunk.iter().map(|(_, lane)| *lane).collect(),
+        })
+        .collect();
+
+    let mut patterns = vec![];
+    let mut push = |kind, run: Range<usize>| {
+        patterns.extend(pattern_of(kind, &rows[run.clone()], rows.get(run.end)));
+    };
+    for run in runs(
+        &rows,
+        |row| row.single().is_some(),
+        |run, next| next.single() == run[run.len() - 1].single(),
+    ) {
+        push(PatternKind::Jack, run);
+    }
+    for run in runs(
+        &rows,
+        |row| row.single().is_some(),
+        |run, next| {
+            let lane = next.single();
+            lane.is_some()
+                && lane != run[run.len() - 1].single()
+                && (run.len() < 2 || lane == run[run.len() - 2].single())
+        },
+    ) {
+        push(PatternKind::Trill, run);
+    }
+    for run in runs(
+        &rows,
+        |row| row.single().is_some(),
+        |run, next| {
+            let (Some(lane), Some(last)) = (next.single(), run[run.len() - 1].single()) else {
+                return false;
+            };
+            match run.len() {
+                1 => lane != last,
+                len => {
+                    let previous = run[len - 2].single().expect("must be single");
+                    lane != last && (lane > last) == (last > previous)
+                }
+            }
+        },
+    ) {
+        push(PatternKind::Roll, run);
+    }
+    for run in runs(
+        &rows,
+        |row| row.lanes.len() > 1,
+        |run, next| {
+            let last = &run[run.len() - 1];
+            !next.shares_lane(last) && (next.lanes.len() > 1 || last.lanes.len() > 1)
+        },
+    ) {
+        let jumps = rows[run.clone()]
+            .iter()
+            .filter(|row| row.lanes.len() > 1)
+            .count();
+        if 0 < jumps && jumps < run.len() {
+            push(PatternKind::Jumpstream, run);
+        }
+    }
+    for run in runs(
+        &rows,
+        |row| row.lanes.len() > 1,
+        |run, next| next.lanes.len() > 1 && next.shares_lane(&run[run.len() - 1]),
+    ) {
+        push(PatternKind::Chordjack, run);
+    }
+
+    patterns.extend(long_notes(chart, clock, &rows));
+    patterns.into_iter().collect()
+}
+
+/// Finds index ranges of maximal runs of at least `MIN_PATTERN_ROWS` rows.
+/// A run begins at a row satisfying `begins`, and continues while `continues` holds.
+fn runs(
+    rows: &[Row],
+    begins: impl Fn(&Row) -> bool,
+    continues: impl Fn(&[Row], &Row) -> bool,
+) -> Vec<Range<usize>> {
+    let mut result = vec![];
+    let mut start = 0;
+    while start < rows.len() {
+        if !begins(&rows[start]) {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < rows.len() && continues(&rows[start..end], &rows[end]) {
+            end += 1;
+        }
+        if end - start >= MIN_PATTERN_ROWS {
+            result.push(start..end);
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    result
+}
+
+fn pattern_of(
+    kind: PatternKind,
+    run: &[Row],
+    next: Option<&Row>,
+) -> Option<(Instant, Instant, Pattern)> {
+    let [first, .., previous, last] = run else {
+        return None;
+    };
+    let notes = run.iter().map(|row| row.lanes.len()).sum();
+    let intensity = intensity(notes - last.lanes.len(), first.seconds, last.seconds)?;
+    let interval = last.time.to_measures() - previous.time.to_measures();
+    let mut end = Instant::from_measures(last.time.to_measures() + interval);
+    if let Some(next) = next {
+        end = end.min(next.time);
+    }
+    Some((
+        first.time,
+        end,
+        Pattern {
+            kind,
+            notes,
+            intensity,
+        },
+    ))
+}
+
+/// Finds sections covered by overlapping holds, spanning from the first head to the last end.
+fn long_notes(chart: &Chart, clock: &ChartClock, rows: &[Row]) -> Vec<(Instant, Instant, Pattern)> {
+    let mut holds: Vec<_> = chart
+        .notes()
+        .filter_map(|(time, note)| Some((time, note.hold_end()?)))
+        .collect();
+    holds.sort();
+
+    let mut sections: Vec<(Instant, Instant, usize)> = vec![];
+    for (start, end) in holds {
+        match sections.last_mut() {
+            Some(section) if start < section.1 => {
+                section.1 = section.1.max(end);
+                section.2 += 1;
+            }
+            _ => sections.push((start, end, 1)),
+        }
+    }
+
+    sections
+        .into_iter()
+        .filter(|(_, _, holds)| *holds >= MIN_PATTERN_ROWS)
+        .filter_map(|(start, end, _)| {
+            let inside = || {
+                rows.iter()
+                    .filter(|row| start <= row.time && row.time < end)
+            };
+            let notes = inside().map(|row| row.lanes.len()).sum();
+            let first = inside().next()?;
+            let intensity = intensity(notes, first.seconds, clock.seconds_at(end))?;
+            Some((
+                start,
+                end,
+                Pattern {
+                    kind: PatternKind::LongNote,
+                    notes,
+                    intensity,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Returns notes per second between seconds, or `None` if no time passes.
+fn intensity(notes: usize, start: Ratio<usize>, end: Ratio<usize>) -> Option<Ratio<usize>> {
+    let duration = end - start;
+    (duration > Ratio::from_integer(0)).then(|| Ratio::from_integer(notes) / duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recognize, PatternKind, MIN_PATTERN_ROWS};
+    use crate::{
+        fixture::{self, hold},
+        instant,
+        note::NoteKind,
+        time::Instant,
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn pattern_recognition_works() {
+        // a measure is 2 seconds; rows of 1/8 measure are 1/4 seconds apart
+        let rows: [&[usize]; 20] = [
+            // jack
+            &[0],
+            &[0],
+            &[0],
+            &[0],
+            // trill
+            &[1],
+            &[2],
+            &[1],
+            &[2],
+            // roll
+            &[0],
+            &[1],
+            &[2],
+            &[3],
+            // jumpstream
+            &[0, 1],
+            &[2],
+            &[0, 3],
+            &[1],
+            // chordjack
+            &[0, 1],
+            &[1, 2],
+            &[2, 3],
+            &[0, 3],
+        ];
+        let mut notes = vec![];
+        for (i, lanes) in rows.iter().enumerate() {
+            for &lane in lanes.iter() {
+                notes.push((Instant::new_parts(i / 8, i % 8, 8), lane, NoteKind::Tap));
+            }
+        }
+        for lane in 0..4 {
+            notes.push((Instant::new_parts(3, lane, 8), lane, hold(instant![4:0/1])));
+        }
+        let (chart, clock) = fixture::chart(4, &notes);
+
+        let patterns = recognize(&chart, &clock);
+        let found: Vec<_> = patterns
+            .spans()
+            .map(|(start, end, p)| (p.kind, start, end, p.notes))
+            .collect();
+        assert!(found.contains(&(PatternKind::Jack, instant![0:0/1], instant![0:4/8], 4)));
+        assert!(found.contains(&(PatternKind::Trill, instant![0:4/8], instant![1:0/1], 4)));
+        assert!(found.contains(&(PatternKind::Roll, instant![1:0/1], instant![1:4/8], 4)));
+        assert!(found.contains(&(PatternKind::Jumpstream, instant![1:4/8], instant![2:0/1], 6)));
+        assert!(found.contains(&(PatternKind::Chordjack, instant![2:0/1], instant![2:4/8], 8)));
+        assert!(found.contains(&(PatternKind::LongNote, instant![3:0/1], instant![4:0/1], 4)));
+
+        // the last row of each pattern is covered
+        for (kind, last) in [
+            (PatternKind::Jack, instant![0:3/8]),
+            (PatternKind::Trill, instant![0:7/8]),
+            (PatternKind::Roll, instant![1:3/8]),
+            (PatternKind::Jumpstream, instant![1:7/8]),
+            (PatternKind::Chordjack, instant![2:3/8]),
+        ] {
+            assert!(
+                patterns
+                    .active_at(last)
+                    .iter()
+                    .any(|(_, _, p)| p.kind == kind),
+                "{:?} covers its last row",
+                kind
+            );
+        }
+
+        let jack = patterns
+            .spans()
+            .find(|(_, _, p)| p.kind == PatternKind::Jack);
+        assert_eq!(jack.unwrap().2.intensity, Ratio::from_integer(4));
+        assert!(
+            !found.contains(&(PatternKind::Roll, instant![0:4/8], instant![0:7/8], 4)),
+            "a trill is not a roll"
+        );
+    }
+
+    #[test]
+    fn pattern_recognition_of_edge_charts_works() {
+        let (empty, clock) = fixture::chart(4, &[]);
+        assert!(recognize(&empty, &clock).is_empty());
+
+        let (single, clock) = fixture::chart(4, &[(instant![1:0/1], 0, NoteKind::Tap)]);
+        assert!(recognize(&single, &clock).is_empty());
+
+        // a jack of the minimum rows, ending at the end of the chart, and one row short of it
+        let jack = |rows: usize| -> Vec<_> {
+            (0..rows)
+                .map(|i| (Instant::new_parts(1, i, 8), 0, NoteKind::Tap))
+                .collect()
+        };
+        let (chart, clock) = fixture::chart(4, &jack(MIN_PATTERN_ROWS));
+        let patterns = recognize(&chart, &clock);
+        let found: Vec<_> = patterns
+            .spans()
+            .map(|(start, end, p)| (p.kind, start, end, p.notes))
+            .collect();
+        let end = Instant::new_parts(1, MIN_PATTERN_ROWS, 8);
+        assert_eq!(
+            found,
+            vec![(PatternKind::Jack, instant![1:0/1], end, MIN_PATTERN_ROWS)],
+            "one more interval after the last row"
+        );
+        let last = Instant::new_parts(1, MIN_PATTERN_ROWS - 1, 8);
+        assert_eq!(patterns.active_at(last).len(), 1);
+        let (chart, clock) = fixture::chart(4, &jack(MIN_PATTERN_ROWS - 1));
+        assert!(recognize(&chart, &clock).is_empty());
+    }
+}
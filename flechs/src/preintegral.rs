@@ -1,5 +1,22 @@
 use crate::{time::TimeUnit, timeline::Timeline, util::upper_bound};
 
+use thiserror::Error as ThisError;
+
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum PreintegralError {
+    /// Timeline has no item.
+    #[error("empty timeline")]
+    Empty,
+
+    /// Integrated value exceeds the range of output.
+    #[error("integration overflowed")]
+    Overflow,
+
+    /// Time is before the first item.
+    #[error("time is before the first item")]
+    BeforeStart,
+}
+
 /// Indicates that this element is integrable.
 pub trait Integrable<U>
 where
@@ -17,6 +34,18 @@ where
     fn zero() -> Self::Output;
 }
 
+/// Indicates that this element is integrable with overflow detection.
+pub trait CheckedIntegrable<U>: Integrable<U>
+where
+    U: TimeUnit,
+{
+    /// Integrates the value, or returns `None` on overflow.
+    fn checked_integrate_within(&self, self_time: U, target_time: U) -> Option<Self::Output>;
+
+    /// Accumlates the value, or returns `None` on overflow.
+    fn checked_accumlate(lhs: Self::Output, rhs: Self::Output) -> Option<Self::Output>;
+}
+
 #[derive(Debug, Clone)]
 pub struct Preintegral<U, V>
 where
@@ -58,11 +87,83 @@ where
         }
     }
 
+    /// Creates preintegral, detecting overflow.
+    pub fn try_new(timeline: Timeline<U, V>) -> Result<Preintegral<U, V>, PreintegralError>
+    where
+        V: CheckedIntegrable<U>,
+    {
+        let mut pairs = timeline.into_pairs();
+        let (first_time, first_value) = pairs.next().ok_or(PreintegralError::Empty)?;
+
+        let mut times = vec![first_time];
+        let mut items = vec![first_value];
+        let mut integrated_values = vec![V::zero()];
+        for (time, value) in pairs {
+            let last_time = times.last().expect("must have item");
+            let last_value = items.last().expect("must have item");
+            let last_integral = integrated_values.last().expect("must have item");
+            let integrated_value = last_value
+                .checked_integrate_within(*last_time, time)
+                .and_then(|section| V::checked_accumlate(last_integral.clone(), section))
+                .ok_or(PreintegralError::Overflow)?;
+            times.push(time);
+            items.push(value);
+            integrated_values.push(integrated_value);
+        }
+
+        Ok(Preintegral {
+            times,
+            integrated_values,
+            items,
+        })
+    }
+
+    /// Returns iterator of sections; start time, item and integrated value at start.
+    pub fn sections(&self) -> impl Iterator<Item = (U, &V, &V::Output)> {
+        self.times
+            .iter()
+            .zip(self.items.iter())
+            .zip(self.integrated_values.iter())
+            .map(|((t, i), v)| (*t, i, v))
+    }
+
+    /// Returns the item of the section which contains specified time.
+    pub fn item_at(&self, time: U) -> &V {
+        &self.items[upper_bound(&self.times, &time) - 1]
+    }
+
+    /// Returns the section in which the integral reaches specified value;
+    /// start time, item and integrated value at start.
+    /// Integrated values must be non-decreasing.
+    pub fn section_by_value(&self, value: &V::Output) -> (U, &V, &V::Output)
+    where
+        V::Output: PartialOrd,
+    {
+        let base = upper_bound(&self.integrated_values, value).max(1) - 1;
+        (
+            self.times[base],
+            &self.items[base],
+            &self.integrated_values[base],
+        )
+    }
+
     pub fn fetch(&self, time: U) -> V::Output {
         let base = upper_bound(&self.times, &time) - 1;
         let section = self.items[base].integrate_within(self.times[base], time);
-        let sum = V::accumlate(self.integrated_values[base].clone(), section);
+        V::accumlate(self.integrated_values[base].clone(), section)
+    }
 
-        sum
+    /// Fetches integrated value, detecting overflow and time before the first item.
+    pub fn try_fetch(&self, time: U) -> Result<V::Output, PreintegralError>
+    where
+        V: CheckedIntegrable<U>,
+    {
+        let base = upper_bound(&self.times, &time)
+            .checked_sub(1)
+            .ok_or(PreintegralError::BeforeStart)?;
+        self.items[base]
+            .checked_integrate_within(self.times[base], time)
+            .and_then(|section| V::checked_accumlate(self.integrated_values[base].clone(), section))
+            .ok_or(PreintegralError::Overflow)
     }
 }
@@ -0,0 +1,163 @@
+//! Contains render list query.
+
+use crate::{
+    chart::Chart,
+    note::{LaneId, Note},
+    preintegral::Preintegral,
+    time::Instant,
+    util::signed_ratio,
+    value::ScrollChange,
+};
+
+use num::{rational::Ratio, ToPrimitive, Zero};
+
+/// Represents display settings for render list query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderSettings {
+    /// Hi-speed multiplier.
+    pub hispeed: Ratio<usize>,
+
+    /// Visible lane length in scroll position (beats at 1x hi-speed).
+    pub lane_length: Ratio<usize>,
+}
+
+/// Represents a note visible on screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderedNote {
+    /// Lane of the note.
+    pub lane: LaneId,
+
+    /// Time of the note (the head for hold notes).
+    pub time: Instant,
+
+    /// The note.
+    pub note: Note,
+
+    /// Normalized position; 0 on judge line, 1 on the top of lane.
+    /// Clipped to 0 for hold notes whose head has passed.
+    pub y: f64,
+
+    /// Normalized position of hold end, clipped to 1.
+    pub end_y: Option<f64>,
+}
+
+/// Fills `buffer` with notes visible at `now`, lane by lane. Invisible notes are skipped.
+/// `buffer` is cleared first and never reallocated once it is large enough.
+///
+/// A note is visible in `[enter, time)`, and a hold note in `[enter, end)`,
+/// the same as the intervals of `VisibilityIndex`; a note at `now` has been passed.
+///
+/// Notes are scanned from `now` until one goes beyond the top of lane,
+/// so scroll speed must not be negative. Use `VisibilityIndex` for reverse scroll.
+pub fn render_notes(
+    chart: &Chart,
+    scroll: &Preintegral<Instant, ScrollChange>,
+    settings: RenderSettings,
+    now: Instant,
+    buffer: &mut Vec<RenderedNote>,
+) {
+    buffer.clear();
+
+    let origin = scroll.fetch(now);
+    let scale = signed_ratio(settings.hispeed / settings.lane_length);
+    let normalize = |time: Instant| (scroll.fetch(time) - origin) * scale;
+    let to_f64 = |y: Ratio<isize>| y.to_f64().expect("must be representable");
+    let clip_end = |end: Instant| to_f64(normalize(end).min(Ratio::from_integer(1)));
+
+    for (lane, notes) in chart.lanes() {
+        if let Some(holds) = chart.holds(lane) {
+            holds.for_each_active_at(now, |start, end, note| {
+                if !note.is_rendered() {
+                    return;
+                }
+                buffer.push(RenderedNote {
+                    lane,
+                    time: start,
+                    note: *note,
+                    y: 0.0,
+                    end_y: Some(clip_end(end)),
+                });
+            });
+        }
+
+        for (time, note) in notes.range_from(now).skip_while(|(time, _)| *time == now) {
+            let y = normalize(time);
+            if y > Ratio::from_integer(1) {
+                break;
+            }
+            if !note.is_rendered() {
+                continue;
+            }
+            buffer.push(RenderedNote {
+                lane,
+                time,
+                note: *note,
+                y: to_f64(y.max(Ratio::zero())),
+                end_y: note.hold_end().map(clip_end),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_notes, RenderSettings};
+    use crate::{
+        chart::Chart,
+        instant,
+        note::{LaneId, Note, NoteKind},
+        preintegral::Preintegral,
+        timeline,
+        value::{merge_beats_and_scroll, Beat, RhythmChange, Scroll, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn render_list_works() {
+        let rhythm = timeline! {
+            [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+        };
+        let mut chart = Chart::new(rhythm, 2);
+        let tap = Note::new(LaneId(0), NoteKind::Tap);
+        let hold = Note::new(
+            LaneId(1),
+            NoteKind::Hold {
+                end: instant![3:0/1],
+            },
+        );
+        chart.insert_note(instant![0:1/2], tap).unwrap();
+        chart.insert_note(instant![1:0/1], tap).unwrap();
+        chart.insert_note(instant![1:1/2], tap).unwrap();
+        chart.insert_note(instant![2:0/1], tap).unwrap();
+        chart.insert_note(instant![0:3/4], hold).unwrap();
+        chart
+            .insert_note(instant![1:1/4], Note::new(LaneId(0), NoteKind::Invisible))
+            .unwrap();
+
+        let beats = timeline! { [0]: Beat(Ratio::new(4, 1)), };
+        let scrolls = timeline! {
+            [0:0/1]: Scroll(Ratio::new(1, 1)),
+            [1:1/4]: Scroll(Ratio::new(2, 1)),
+        };
+        let scroll = Preintegral::new(merge_beats_and_scroll(beats, scrolls).unwrap());
+        let settings = RenderSettings {
+            hispeed: Ratio::new(2, 1),
+            lane_length: Ratio::new(8, 1),
+        };
+
+        let mut buffer = vec![];
+        render_notes(&chart, &scroll, settings, instant![1:0/1], &mut buffer);
+        let positions: Vec<_> = buffer
+            .iter()
+            .map(|r| (r.lane, r.time, r.y, r.end_y))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (LaneId(0), instant![1:1/2], 0.75, None),
+                (LaneId(1), instant![0:3/4], 0.0, Some(1.0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! Contains replay recording and deterministic playback.
+//!
+//! Replays are stored as text, one field or input event per line:
+//!
+//! ```text
+//! flechs-replay 1
+//! chart 0123456789abcdef
+//! seed 42
+//! lanes random
+//! windows iidx
+//! holds cn
+//! gauge hard
+//! rate 3/2
+//! audio-offset -1/100
+//! press 0 2/1
+//! release 0 11/4
+//! ```
+
+use crate::{
+    chart::Chart,
+    clock::{ChartClock, Offsets},
+    gauge::{Gauge, GaugeKind},
+    judge::{HoldMode, HoldRules, InputAction, InputEvent, Judge, Judgment, WindowPreset},
+    note::{LaneId, LaneKind},
+    score::{ExScore, Scoring, Tally},
+    time::SignedSeconds,
+    transform::{transform, LaneOption, TransformError},
+};
+
+use std::{fmt, str::FromStr};
+
+use num::rational::Ratio;
+use thiserror::Error as ThisError;
+
+const HEADER: &str = "flechs-replay 1";
+
+/// The shortest HCN tick interval in measures, bounding the number of ticks.
+const MIN_HCN_TICK: Ratio<usize> = Ratio::new_raw(1, 192);
+
+/// Represents an error about `Replay`.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum ReplayError {
+    /// The text is not a replay of supported version.
+    #[error("invalid replay header")]
+    InvalidHeader,
+
+    /// The line cannot be parsed, or has an invalid value.
+    #[error("invalid replay line {0}: {1:?}")]
+    InvalidLine(usize, String),
+
+    /// The header line of specified field is missing.
+    #[error("missing replay line {0:?}")]
+    MissingLine(&'static str),
+
+    /// The lane option cannot be applied to the chart.
+    #[error("lane option cannot be applied: {0}")]
+    Transform(TransformError),
+
+    /// The replay is recorded on another chart.
+    #[error("chart hash mismatch; expected {expected:016x}, found {found:016x}")]
+    ChartMismatch { expected: u64, found: u64 },
+
+    /// The clock changes playback rate, which the replay cannot record.
+    #[error("clock with rate changes cannot be replayed")]
+    RateChanges,
+
+    /// An input event refers a lane which chart doesn't have.
+    #[error("input lane {0:?} is out of range")]
+    LaneOutOfRange(LaneId),
+}
+
+/// Represents play options affecting judgment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayOptions {
+    /// Lane option on key lanes, seeded by the replay.
+    pub lane_option: LaneOption,
+    pub windows: WindowPreset,
+
+    /// Hold mode. The release uses the same windows.
+    pub hold_mode: HoldMode,
+    pub gauge: GaugeKind,
+    pub rate: Ratio<usize>,
+
+    /// User audio offset; chart offset comes from the clock.
+    pub audio_offset: Ratio<isize>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            lane_option: LaneOption::Off,
+            windows: WindowPreset::Iidx,
+            hold_mode: HoldMode::Ln,
+            gauge: GaugeKind::Normal,
+            rate: Ratio::from_integer(1),
+            audio_offset: Ratio::from_integer(0),
+        }
+    }
+}
+
+/// Represents the result of replay playback.
+#[derive(Debug, Clone)]
+pub struct ReplayResult {
+    pub judgments: Vec<Judgment>,
+    pub tally: Tally,
+    pub ex_score: ExScore,
+    pub gauge: Gauge,
+}
+
+/// Represents a recorded play.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub chart_hash: u64,
+    pub seed: u64,
+    pub options: ReplayOptions,
+
+    /// Input events in order of seconds.
+    pub inputs: Vec<InputEvent>,
+}
+
+impl Replay {
+    /// Creates new empty replay of the chart.
+    pub fn new(chart: &Chart, options: ReplayOptions, seed: u64) -> Replay {
+        Replay {
+            chart_hash: chart.stable_hash(),
+            seed,
+            options,
+            inputs: vec![],
+        }
+    }
+
+    /// Records an input event. Panics if it is earlier than the last one.
+    pub fn record(&mut self, event: InputEvent) {
+        if let Some(last) = self.inputs.last() {
+            assert!(last.seconds <= event.seconds, "input out of order");
+        }
+        self.inputs.push(event);
+    }
+
+    /// Applies options to the clock of the chart. The rate of the options replaces
+    /// the rate of the clock, so a clock which changes rate is rejected.
+    pub fn clock(&self, clock: &ChartClock) -> Result<ChartClock, ReplayError> {
+        if clock.has_rate_changes() {
+            return Err(ReplayError::RateChanges);
+        }
+        let offsets = Offsets {
+            audio: self.options.audio_offset,
+            ..clock.offsets()
+        };
+        Ok(clock
+            .clone()
+            .with_offsets(offsets)
+            .with_rate(self.options.rate))
+    }
+
+    /// Applies the lane option to key lanes of the chart.
+    pub fn chart(&self, chart: &Chart) -> Result<Chart, TransformError> {
+        let keys: Vec<_> = chart
+            .lane_kinds()
+            .iter()
+            .enumerate()
+            .filter(|(_, kind)| **kind == LaneKind::Key)
+            .map(|(i, _)| LaneId(i))
+            .collect();
+        transform(chart, self.options.lane_option, &keys, self.seed)
+    }
+
+    /// Creates the judge which the replay is played on, for the chart with the lane option applied.
+    pub fn judge(&self, chart: &Chart, clock: &ChartClock) -> Result<Judge, ReplayError> {
+        let windows = self.options.windows.windows();
+        let holds = HoldRules {
+            mode: self.options.hold_mode,
+            release: windows,
+        };
+        Ok(Judge::with_holds(
+            chart,
+            &self.clock(clock)?,
+            windows,
+            holds,
+        ))
+    }
+
+    /// Plays the replay. Results are exact, thus identical on every playback.
+    pub fn play(&self, chart: &Chart, clock: &ChartClock) -> Result<ReplayResult, ReplayError> {
+        let found = chart.stable_hash();
+        if found != self.chart_hash {
+            return Err(ReplayError::ChartMismatch {
+                expected: self.chart_hash,
+                found,
+            });
+        }
+        if let Some(event) = self.inputs.iter().find(|e| e.lane.0 >= chart.lane_count()) {
+            return Err(ReplayError::LaneOutOfRange(event.lane));
+        }
+
+        let chart = self.chart(chart).map_err(ReplayError::Transform)?;
+        let judge = self.judge(&chart, clock)?;
+        let mut gauge = Gauge::new(self.options.gauge, judge.note_count());
+        let judgments = judge.run(self.inputs.iter().copied());
+        let mut tally = Tally::default();
+        let mut ex_score = ExScore::default();
+        tally.judge_all(&judgments);
+        ex_score.judge_all(&judgments);
+        gauge.judge_all(&judgments);
+
+        Ok(ReplayResult {
+            judgments,
+            tally,
+            ex_score,
+            gauge,
+        })
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = &self.options;
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "chart {:016x}", self.chart_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "lanes {}", lane_option_name(options.lane_option))?;
+        match options.windows {
+            WindowPreset::Iidx => writeln!(f, "windows iidx")?,
+            WindowPreset::Lr2 => writeln!(f, "windows lr2")?,
+            WindowPreset::Beatoraja => writeln!(f, "windows beatoraja")?,
+            WindowPreset::OsuMania(od) => writeln!(f, "windows osu-mania {}", od)?,
+            WindowPreset::StepMania => writeln!(f, "windows stepmania")?,
+        }
+        match options.hold_mode {
+            HoldMode::Ln => writeln!(f, "holds ln")?,
+            HoldMode::Cn => writeln!(f, "holds cn")?,
+            HoldMode::Hcn { tick } => writeln!(f, "holds hcn {}", tick)?,
+        }
+        writeln!(f, "gauge {}", gauge_name(options.gauge))?;
+        writeln!(f, "rate {}", options.rate)?;
+        writeln!(f, "audio-offset {}", options.audio_offset)?;
+        for event in &self.inputs {
+            let action = match event.action {
+                InputAction::Press => "press",
+                InputAction::Release => "release",
+            };
+            writeln!(f, "{} {} {}", action, event.lane.0, event.seconds.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = ReplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some(HEADER) {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let mut chart_hash = None;
+        let mut seed = None;
+        let mut lane_option = None;
+        let mut windows = None;
+        let mut hold_mode = None;
+        let mut gauge = None;
+        let mut rate = None;
+        let mut audio_offset = None;
+        let mut inputs = vec![];
+        for (index, line) in lines {
+            let invalid = || ReplayError::InvalidLine(index + 1, line.to_string());
+            let words: Vec<_> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["chart", hash] => {
+                    chart_hash = Some(u64::from_str_radix(hash, 16).map_err(|_| invalid())?)
+                }
+                ["seed", value] => seed = Some(value.parse().map_err(|_| invalid())?),
+                ["lanes", name] => lane_option = Some(parse_lane_option(name).ok_or_else(invalid)?),
+                ["windows", "iidx"] => windows = Some(WindowPreset::Iidx),
+                ["windows", "lr2"] => windows = Some(WindowPreset::Lr2),
+                ["windows", "beatoraja"] => windows = Some(WindowPreset::Beatoraja),
+                ["windows", "osu-mania", od] => {
+                    let od: Ratio<usize> = od.parse().map_err(|_| invalid())?;
+                    if od > Ratio::from_integer(10) {
+                        return Err(invalid());
+                    }
+                    windows = Some(WindowPreset::OsuMania(od));
+                }
+                ["windows", "stepmania"] => windows = Some(WindowPreset::StepMania),
+                ["holds", "ln"] => hold_mode = Some(HoldMode::Ln),
+                ["holds", "cn"] => hold_mode = Some(HoldMode::Cn),
+                ["holds", "hcn", tick] => {
+                    let tick: Ratio<usize> = tick.parse().map_err(|_| invalid())?;
+                    if tick < MIN_HCN_TICK {
+                        return Err(invalid());
+                    }
+                    hold_mode = Some(HoldMode::Hcn { tick });
+                }
+                ["gauge", name] => gauge = Some(parse_gauge(name).ok_or_else(invalid)?),
+                ["rate", value] => {
+                    let value: Ratio<usize> = value.parse().map_err(|_| invalid())?;
+                    if value == Ratio::from_integer(0) {
+                        return Err(invalid());
+                    }
+                    rate = Some(value);
+                }
+                ["audio-offset", offset] => {
+                    audio_offset = Some(offset.parse().map_err(|_| invalid())?)
+                }
+                [action @ ("press" | "release"), lane, seconds] => {
+                    let seconds = SignedSeconds(seconds.parse().map_err(|_| invalid())?);
+                    if inputs
+                        .last()
+                        .is_some_and(|e: &InputEvent| e.seconds > seconds)
+                    {
+                        return Err(invalid());
+                    }
+                    inputs.push(InputEvent {
+                        lane: LaneId(lane.parse().map_err(|_| invalid())?),
+                        action: if *action == "press" {
+                            InputAction::Press
+                        } else {
+                            InputAction::Release
+                        },
+                        seconds,
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let missing = ReplayError::MissingLine;
+        Ok(Replay {
+            chart_hash: chart_hash.ok_or(missing("chart"))?,
+            seed: seed.ok_or(missing("seed"))?,
+            options: ReplayOptions {
+                lane_option: lane_option.ok_or(missing("lanes"))?,
+                windows: windows.ok_or(missing("windows"))?,
+                hold_mode: hold_mode.ok_or(missing("holds"))?,
+                gauge: gauge.ok_or(missing("gauge"))?,
+                rate: rate.ok_or(missing("rate"))?,
+                audio_offset: audio_offset.ok_or(missing("audio-offset"))?,
+            },
+            inputs,
+        })
+    }
+}
+
+const GAUGES: [(GaugeKind, &str); 6] = [
+    (GaugeKind::AssistEasy, "assist-easy"),
+    (GaugeKind::Easy, "easy"),
+    (GaugeKind::Normal, "normal"),
+    (GaugeKind::Hard, "hard"),
+    (GaugeKind::ExHard, "ex-hard"),
+    (GaugeKind::Hazard, "hazard"),
+];
+
+fn gauge_name(kind: GaugeKind) -> &'static str {
+    GAUGES
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, name)| *name)
+        .expect("must be listed")
+}
+
+fn parse_gauge(name: &str) -> Option<GaugeKind> {
+    GAUGES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(kind, _)| *kind)
+}
+
+const LANE_OPTIONS: [(LaneOption, &str); 5] = [
+    (LaneOption::Off, "off"),
+    (LaneOption::Mirror, "mirror"),
+    (LaneOption::Random, "random"),
+    (LaneOption::RRandom, "r-random"),
+    (LaneOption::SRandom, "s-random"),
+];
+
+fn lane_option_name(option: LaneOption) -> &'static str {
+    LANE_OPTIONS
+        .iter()
+        .find(|(o, _)| *o == option)
+        .map(|(_, name)| *name)
+        .expect("must be listed")
+}
+
+fn parse_lane_option(name: &str) -> Option<LaneOption> {
+    LANE_OPTIONS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(option, _)| *option)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, ReplayError, ReplayOptions};
+    use crate::{
+        fixture::{self, hold},
+        gauge::GaugeKind,
+        instant,
+        judge::{HoldMode, InputAction, InputEvent, WindowPreset},
+        note::{KeysoundId, LaneId, NoteKind},
+        score::ExScore,
+        time::SignedSeconds,
+        transform::LaneOption,
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn replay_round_trip_works() {
+        let (chart, clock) = fixture::chart(
+            2,
+            &[
+                (instant![1:0/1], 0, NoteKind::Tap),
+                (instant![1:1/2], 1, hold(instant![2:0/1])),
+            ],
+        );
+
+        let options = ReplayOptions {
+            lane_option: LaneOption::Mirror,
+            windows: WindowPreset::OsuMania(Ratio::new(17, 2)),
+            hold_mode: HoldMode::Hcn {
+                tick: Ratio::new(1, 4),
+            },
+            gauge: GaugeKind::Hard,
+            rate: Ratio::new(3, 2),
+            audio_offset: Ratio::new(-1, 100),
+        };
+        let mut replay = Replay::new(&chart, options, 42);
+        for (lane, action, seconds) in [
+            (1, InputAction::Press, Ratio::new(133, 100)),
+            (1, InputAction::Release, Ratio::new(14, 10)),
+            (0, InputAction::Press, Ratio::new(2, 1)),
+            (0, InputAction::Release, Ratio::new(8, 3)),
+        ] {
+            replay.record(InputEvent {
+                lane: LaneId(lane),
+                action,
+                seconds: SignedSeconds(seconds),
+            });
+        }
+
+        let text = replay.to_string();
+        let parsed: Replay = text.parse().expect("must parse");
+        assert_eq!(parsed, replay);
+
+        let first = replay.play(&chart, &clock).unwrap();
+        let second = parsed.play(&chart, &clock).unwrap();
+        assert_eq!(first.judgments, second.judgments);
+        assert_eq!(first.tally, second.tally);
+        assert_eq!(first.ex_score, second.ex_score);
+        assert_eq!(first.gauge.value(), second.gauge.value());
+        assert_eq!(first.tally.notes(), 3);
+        assert_eq!(first.ex_score, ExScore(6), "mirrored inputs hit all notes");
+
+        let mut other = chart.clone();
+        other.insert_bgm(instant![0:0/1], KeysoundId(1));
+        assert!(matches!(
+            replay.play(&other, &clock),
+            Err(ReplayError::ChartMismatch { .. })
+        ));
+        assert_eq!(
+            "flechs-replay 1\nholds xcn".parse::<Replay>(),
+            Err(ReplayError::InvalidLine(2, "holds xcn".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_replays_are_rejected() {
+        let header = concat!(
+            "flechs-replay 1\n",
+            "chart 0\nseed 0\nlanes off\n",
+            "windows iidx\nholds ln\ngauge normal\nrate 1\naudio-offset 0\n",
+        );
+        let replay: Replay = header.parse().expect("must parse");
+        assert_eq!(replay.options, ReplayOptions::default());
+        assert!(replay.inputs.is_empty());
+
+        for line in [
+            "rate 0",
+            "windows osu-mania 11",
+            "holds hcn 0",
+            "holds hcn 1/1000000",
+            "lanes shuffle",
+        ] {
+            assert_eq!(
+                format!("{}{}", header, line).parse::<Replay>(),
+                Err(ReplayError::InvalidLine(10, line.to_string())),
+            );
+        }
+        assert!(format!("{}holds hcn 1/192", header)
+            .parse::<Replay>()
+            .is_ok());
+        assert_eq!(
+            format!("{}press 0 2\nrelease 0 1", header).parse::<Replay>(),
+            Err(ReplayError::InvalidLine(11, "release 0 1".to_string())),
+            "inputs out of order"
+        );
+        assert_eq!(
+            "flechs-replay 1\nseed 0\n".parse::<Replay>(),
+            Err(ReplayError::MissingLine("chart"))
+        );
+        assert_eq!(
+            header.replace("gauge normal\n", "").parse::<Replay>(),
+            Err(ReplayError::MissingLine("gauge"))
+        );
+        assert_eq!("".parse::<Replay>(), Err(ReplayError::InvalidHeader));
+    }
+
+    #[test]
+    fn unreplayable_plays_are_rejected() {
+        let (chart, clock) = fixture::chart(2, &[(instant![1:0/1], 0, NoteKind::Tap)]);
+        let mut replay = Replay::new(&chart, ReplayOptions::default(), 0);
+        replay.record(InputEvent {
+            lane: LaneId(0),
+            action: InputAction::Press,
+            seconds: SignedSeconds(Ratio::from_integer(2)),
+        });
+
+        let mut changing = clock.clone();
+        changing.change_rate(Ratio::from_integer(1), Ratio::new(3, 2));
+        assert_eq!(
+            replay.play(&chart, &changing).err(),
+            Some(ReplayError::RateChanges)
+        );
+
+        replay.record(InputEvent {
+            lane: LaneId(2),
+            action: InputAction::Press,
+            seconds: SignedSeconds(Ratio::from_integer(3)),
+        });
+        assert_eq!(
+            replay.play(&chart, &clock).err(),
+            Some(ReplayError::LaneOutOfRange(LaneId(2)))
+        );
+    }
+}
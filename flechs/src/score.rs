@@ -0,0 +1,255 @@
+//! Contains scoring systems consuming judgments.
+
+use crate::judge::{Grade, Judgment, JudgmentKind};
+
+use num::rational::Ratio;
+
+/// Indicates that this consumes judgment stream.
+pub trait Scoring {
+    /// Consumes a judgment.
+    fn judge(&mut self, judgment: &Judgment);
+
+    /// Consumes judgments in order.
+    fn judge_all<'a>(&mut self, judgments: impl IntoIterator<Item = &'a Judgment>) {
+        for judgment in judgments {
+            self.judge(judgment);
+        }
+    }
+}
+
+/// Returns the grade of note or release hit.
+fn note_grade(kind: JudgmentKind) -> Option<Grade> {
+    match kind {
+        JudgmentKind::Hit(grade) | JudgmentKind::Release(grade) => Some(grade),
+        _ => None,
+    }
+}
+
+/// Represents counts of judgments and combo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tally {
+    pub pgreat: usize,
+    pub great: usize,
+    pub good: usize,
+    pub bad: usize,
+    pub poor: usize,
+
+    /// Misses of notes and releases.
+    pub miss: usize,
+    pub empty_poor: usize,
+    pub combo: usize,
+    pub max_combo: usize,
+}
+
+impl Tally {
+    /// Returns the number of judged notes and releases.
+    pub fn notes(&self) -> usize {
+        self.pgreat + self.great + self.good + self.bad + self.poor + self.miss
+    }
+}
+
+impl Scoring for Tally {
+    fn judge(&mut self, judgment: &Judgment) {
+        let count = match judgment.kind {
+            JudgmentKind::Hit(grade) | JudgmentKind::Release(grade) => match grade {
+                Grade::PGreat => &mut self.pgreat,
+                Grade::Great => &mut self.great,
+                Grade::Good => &mut self.good,
+                Grade::Bad => &mut self.bad,
+                Grade::Poor => &mut self.poor,
+            },
+            JudgmentKind::Miss | JudgmentKind::ReleaseMiss => &mut self.miss,
+            JudgmentKind::EmptyPoor => {
+                self.empty_poor += 1;
+                return;
+            }
+            JudgmentKind::HoldTick { .. } => return,
+        };
+        *count += 1;
+
+        if note_grade(judgment.kind).is_some_and(|grade| grade <= Grade::Good) {
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+        } else {
+            self.combo = 0;
+        }
+    }
+}
+
+/// Represents DJ level, rank by EX score rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DjLevel {
+    F,
+    E,
+    D,
+    C,
+    B,
+    A,
+    AA,
+    AAA,
+}
+
+impl DjLevel {
+    /// Returns DJ level of EX score. Same for SP and DP.
+    pub fn new(ex_score: usize, max_ex_score: usize) -> DjLevel {
+        if max_ex_score == 0 {
+            return DjLevel::F;
+        }
+        // the rate in ninths
+        let ninths = Ratio::new(ex_score * 9, max_ex_score).to_integer();
+        match ninths {
+            0 | 1 => DjLevel::F,
+            2 => DjLevel::E,
+            3 => DjLevel::D,
+            4 => DjLevel::C,
+            5 => DjLevel::B,
+            6 => DjLevel::A,
+            7 => DjLevel::AA,
+            _ => DjLevel::AAA,
+        }
+    }
+}
+
+/// EX score; 2 for PGREAT and 1 for GREAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExScore(pub usize);
+
+impl ExScore {
+    /// Returns DJ level for specified number of notes.
+    pub fn dj_level(&self, notes: usize) -> DjLevel {
+        DjLevel::new(self.0, notes * 2)
+    }
+}
+
+impl Scoring for ExScore {
+    fn judge(&mut self, judgment: &Judgment) {
+        self.0 += match note_grade(judgment.kind) {
+            Some(Grade::PGreat) => 2,
+            Some(Grade::Great) => 1,
+            _ => 0,
+        };
+    }
+}
+
+/// Money score of 200000 at most; 150000 by grades and 50000 by max combo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoneyScore {
+    notes: usize,
+    tally: Tally,
+}
+
+impl MoneyScore {
+    pub fn new(notes: usize) -> MoneyScore {
+        MoneyScore {
+            notes,
+            tally: Tally::default(),
+        }
+    }
+
+    /// Returns current score, rounded down.
+    pub fn score(&self) -> usize {
+        if self.notes == 0 {
+            return 0;
+        }
+        let Tally {
+            pgreat,
+            great,
+            good,
+            max_combo,
+            ..
+        } = self.tally;
+        let grades = Ratio::new(150000 * (2 * (pgreat + great) + good), 2 * self.notes);
+        let combo = Ratio::new(50000 * max_combo, self.notes);
+        (grades + combo).to_integer()
+    }
+}
+
+impl Scoring for MoneyScore {
+    fn judge(&mut self, judgment: &Judgment) {
+        self.tally.judge(judgment);
+    }
+}
+
+/// osu!mania accuracy. Grades map to MAX, 300, 200, 100 and 50, and misses count zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OsuAccuracy(pub Tally);
+
+impl OsuAccuracy {
+    /// Returns accuracy in 0 to 1, or one if nothing is judged.
+    pub fn accuracy(&self) -> Ratio<usize> {
+        let tally = &self.0;
+        let notes = tally.notes();
+        if notes == 0 {
+            return Ratio::from_integer(1);
+        }
+        let points = 300 * (tally.pgreat + tally.great)
+            + 200 * tally.good
+            + 100 * tally.bad
+            + 50 * tally.poor;
+        Ratio::new(points, 300 * notes)
+    }
+}
+
+impl Scoring for OsuAccuracy {
+    fn judge(&mut self, judgment: &Judgment) {
+        self.0.judge(judgment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DjLevel, ExScore, MoneyScore, OsuAccuracy, Scoring, Tally};
+    use crate::{
+        instant,
+        judge::{Grade, Judgment, JudgmentKind},
+        note::LaneId,
+        time::SignedSeconds,
+    };
+
+    use num::rational::Ratio;
+
+    fn judgment(kind: JudgmentKind) -> Judgment {
+        Judgment {
+            lane: LaneId(0),
+            time: instant![0:0/1],
+            kind,
+            seconds: SignedSeconds::zero(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn scoring_works() {
+        let judgments: Vec<_> = [
+            JudgmentKind::Hit(Grade::PGreat),
+            JudgmentKind::Hit(Grade::Great),
+            JudgmentKind::EmptyPoor,
+            JudgmentKind::Hit(Grade::Good),
+            JudgmentKind::Miss,
+            JudgmentKind::Hit(Grade::PGreat),
+            JudgmentKind::Release(Grade::PGreat),
+        ]
+        .into_iter()
+        .map(judgment)
+        .collect();
+
+        let mut tally = Tally::default();
+        tally.judge_all(&judgments);
+        assert_eq!(tally.notes(), 6);
+        assert_eq!(tally.max_combo, 3, "empty POOR keeps combo");
+
+        let mut ex = ExScore::default();
+        ex.judge_all(&judgments);
+        assert_eq!(ex, ExScore(7));
+        assert_eq!(ex.dj_level(6), DjLevel::B);
+        assert_eq!(DjLevel::new(16, 18), DjLevel::AAA);
+
+        let mut money = MoneyScore::new(6);
+        money.judge_all(&judgments);
+        assert_eq!(money.score(), 150000 * 9 / 12 + 50000 * 3 / 6);
+
+        let mut osu = OsuAccuracy::default();
+        osu.judge_all(&judgments);
+        assert_eq!(osu.accuracy(), Ratio::new(300 * 4 + 200, 300 * 6));
+    }
+}
@@ -0,0 +1,200 @@
+//! Contains note snap detection and grid quantization.
+
+use crate::{time::Instant, timeline::Timeline, value::Beat};
+
+use num::rational::Ratio;
+
+/// Represents grid which a note snaps to, named after the note value in 4/4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Snap {
+    Fourth,
+    Eighth,
+    Twelfth,
+    Sixteenth,
+    TwentyFourth,
+    ThirtySecond,
+    FortyEighth,
+    SixtyFourth,
+    HundredNinetySecond,
+    OffGrid,
+}
+
+impl Snap {
+    /// Grids in order of coarseness.
+    pub const GRIDS: [Snap; 9] = [
+        Snap::Fourth,
+        Snap::Eighth,
+        Snap::Twelfth,
+        Snap::Sixteenth,
+        Snap::TwentyFourth,
+        Snap::ThirtySecond,
+        Snap::FortyEighth,
+        Snap::SixtyFourth,
+        Snap::HundredNinetySecond,
+    ];
+
+    /// Returns the number of grid lines per beat, or `None` for off-grid.
+    pub const fn divisions_per_beat(&self) -> Option<usize> {
+        match self {
+            Snap::Fourth => Some(1),
+            Snap::Eighth => Some(2),
+            Snap::Twelfth => Some(3),
+            Snap::Sixteenth => Some(4),
+            Snap::TwentyFourth => Some(6),
+            Snap::ThirtySecond => Some(8),
+            Snap::FortyEighth => Some(12),
+            Snap::SixtyFourth => Some(16),
+            Snap::HundredNinetySecond => Some(48),
+            Snap::OffGrid => None,
+        }
+    }
+}
+
+/// Specifies rounding mode of quantization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+
+    /// Rounds to the nearest grid line, ties to later one.
+    Nearest,
+}
+
+impl Instant {
+    /// Classifies submeasure by the coarsest grid it snaps to.
+    /// Grids are counted from measure start, in beats of the measure.
+    pub fn snap(&self, beat: Beat) -> Snap {
+        let denom = *(self.submeasure() * beat.0).denom();
+        Snap::GRIDS
+            .into_iter()
+            .find(|snap| {
+                snap.divisions_per_beat()
+                    .is_some_and(|d| d.is_multiple_of(denom))
+            })
+            .unwrap_or(Snap::OffGrid)
+    }
+
+    /// Classifies submeasure looking up the beat of its measure.
+    pub fn snap_in(&self, beats: &Timeline<usize, Beat>) -> Snap {
+        self.snap(
+            *beats
+                .latest_item(self.measure())
+                .expect("must be zero-aligned"),
+        )
+    }
+
+    /// Quantizes to specified grid. The end of measure counts as a grid line.
+    /// Returns itself for `Snap::OffGrid`.
+    pub fn quantize(&self, beat: Beat, snap: Snap, rounding: Rounding) -> Instant {
+        let Some(divisions) = snap.divisions_per_beat() else {
+            return *self;
+        };
+
+        let position = self.submeasure() * beat.0 * divisions;
+        let floor = position.floor();
+        let ceil = position.ceil().min(beat.0 * divisions);
+        let grid = match rounding {
+            Rounding::Floor => floor,
+            Rounding::Ceil => ceil,
+            Rounding::Nearest if position - floor < ceil - position => floor,
+            Rounding::Nearest => ceil,
+        };
+
+        let submeasure = grid / (beat.0 * divisions);
+        if submeasure >= Ratio::from_integer(1) {
+            Instant::new_parts(self.measure() + 1, 0, 1)
+        } else {
+            Instant::new(self.measure(), submeasure).expect("must be in measure")
+        }
+    }
+
+    /// Quantizes looking up the beat of its measure.
+    pub fn quantize_in(
+        &self,
+        beats: &Timeline<usize, Beat>,
+        snap: Snap,
+        rounding: Rounding,
+    ) -> Instant {
+        let beat = *beats
+            .latest_item(self.measure())
+            .expect("must be zero-aligned");
+        self.quantize(beat, snap, rounding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rounding, Snap};
+    use crate::{instant, timeline, value::Beat};
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn snap_detection_works() {
+        let four = Beat(Ratio::new(4, 1));
+        assert_eq!(instant![0:0/1].snap(four), Snap::Fourth);
+        assert_eq!(instant![0:3/4].snap(four), Snap::Fourth);
+        assert_eq!(instant![0:1/8].snap(four), Snap::Eighth);
+        assert_eq!(instant![0:1/12].snap(four), Snap::Twelfth);
+        assert_eq!(instant![0:5/16].snap(four), Snap::Sixteenth);
+        assert_eq!(instant![0:1/24].snap(four), Snap::TwentyFourth);
+        assert_eq!(instant![0:3/32].snap(four), Snap::ThirtySecond);
+        assert_eq!(instant![0:1/48].snap(four), Snap::FortyEighth);
+        assert_eq!(instant![0:1/64].snap(four), Snap::SixtyFourth);
+        assert_eq!(instant![0:1/96].snap(four), Snap::HundredNinetySecond);
+        assert_eq!(instant![0:1/192].snap(four), Snap::HundredNinetySecond);
+        assert_eq!(instant![0:1/20].snap(four), Snap::OffGrid);
+
+        // 7/8 measure; 2/7 of measure is the second beat
+        let beats = timeline! {
+            [0]: Beat(Ratio::new(4, 1)),
+            [1]: Beat(Ratio::new(7, 2)),
+        };
+        assert_eq!(instant![1:2/7].snap_in(&beats), Snap::Fourth);
+        assert_eq!(instant![1:1/7].snap_in(&beats), Snap::Eighth);
+        assert_eq!(instant![1:1/4].snap_in(&beats), Snap::ThirtySecond);
+    }
+
+    #[test]
+    fn quantization_works() {
+        let four = Beat(Ratio::new(4, 1));
+        let time = instant![2:5/32];
+        assert_eq!(
+            time.quantize(four, Snap::Eighth, Rounding::Floor),
+            instant![2:1/8]
+        );
+        assert_eq!(
+            time.quantize(four, Snap::Eighth, Rounding::Ceil),
+            instant![2:1/4]
+        );
+        assert_eq!(
+            time.quantize(four, Snap::Eighth, Rounding::Nearest),
+            instant![2:1/8]
+        );
+        assert_eq!(
+            instant![2:3/16].quantize(four, Snap::Eighth, Rounding::Nearest),
+            instant![2:1/4],
+            "ties to later"
+        );
+        assert_eq!(
+            instant![2:31/32].quantize(four, Snap::Fourth, Rounding::Nearest),
+            instant![3:0/1],
+            "measure end is a grid line"
+        );
+
+        // 7/8 measure; the last beat is an eighth
+        let seven = Beat(Ratio::new(7, 2));
+        assert_eq!(
+            instant![0:13/14].quantize(seven, Snap::Fourth, Rounding::Nearest),
+            instant![1:0/1]
+        );
+        assert_eq!(
+            instant![0:13/14].quantize(seven, Snap::Fourth, Rounding::Floor),
+            instant![0:6/7]
+        );
+        assert_eq!(
+            instant![0:1/7].quantize(seven, Snap::OffGrid, Rounding::Floor),
+            instant![0:1/7]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Contains span (interval) timeline.
+
+use crate::{time::TimeUnit, util::upper_bound};
+
+/// Represents a timeline of half-open `[start, end)` spans.
+///
+/// Spans are kept sorted by start time and indexed by an implicit interval tree
+/// (a balanced tree over the sorted array, each node holding the maximum end of its subtree),
+/// so that queries run in `O(log n + k)`.
+#[derive(Debug, Clone)]
+pub struct SpanTimeline<U, V> {
+    starts: Vec<U>,
+    ends: Vec<U>,
+    items: Vec<V>,
+    max_ends: Vec<U>,
+}
+
+impl<U, V> SpanTimeline<U, V>
+where
+    U: TimeUnit,
+{
+    /// Creates empty span timeline.
+    pub fn new() -> SpanTimeline<U, V> {
+        SpanTimeline {
+            starts: vec![],
+            ends: vec![],
+            items: vec![],
+            max_ends: vec![],
+        }
+    }
+
+    /// Returns the number of spans.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Returns whether this timeline has no spans.
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Returns iterator of spans in start order.
+    pub fn spans(&self) -> impl Iterator<Item = (U, U, &V)> {
+        self.starts
+            .iter()
+            .zip(self.ends.iter())
+            .zip(self.items.iter())
+            .map(|((s, e), v)| (*s, *e, v))
+    }
+
+    /// Inserts a span. `start` must be earlier than `end`, or will panic.
+    /// Rebuilds the index, so bulk insertion should use `Extend` or `FromIterator`.
+    pub fn insert(&mut self, start: U, end: U, item: V) {
+        self.insert_unindexed(start, end, item);
+        self.rebuild_index();
+    }
+
+    /// Calls `f` for each span active at `time`, in start order.
+    pub fn for_each_active_at<'a>(&'a self, time: U, mut f: impl FnMut(U, U, &'a V)) {
+        self.visit(0, self.len(), time, &|s| *s <= time, &mut f);
+    }
+
+    /// Calls `f` for each span overlapping `[start, end)`, in start order.
+    pub fn for_each_overlapping<'a>(&'a self, start: U, end: U, mut f: impl FnMut(U, U, &'a V)) {
+        self.visit(0, self.len(), start, &|s| *s < end, &mut f);
+    }
+
+    /// Returns all spans active at `time`.
+    pub fn active_at(&self, time: U) -> Vec<(U, U, &V)> {
+        let mut result = vec![];
+        self.for_each_active_at(time, |s, e, v| result.push((s, e, v)));
+        result
+    }
+
+    /// Returns all spans overlapping `[start, end)`.
+    pub fn overlapping(&self, start: U, end: U) -> Vec<(U, U, &V)> {
+        let mut result = vec![];
+        self.for_each_overlapping(start, end, |s, e, v| result.push((s, e, v)));
+        result
+    }
+
+    fn insert_unindexed(&mut self, start: U, end: U, item: V) {
+        assert!(start < end, "invalid span");
+
+        let target_index = upper_bound(&self.starts, &start);
+        self.starts.insert(target_index, start);
+        self.ends.insert(target_index, end);
+        self.items.insert(target_index, item);
+        self.max_ends.push(end);
+    }
+
+    fn rebuild_index(&mut self) {
+        self.rebuild_range(0, self.len());
+    }
+
+    fn rebuild_range(&mut self, lo: usize, hi: usize) -> Option<U> {
+        if lo >= hi {
+            return None;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let mut max_end = self.ends[mid];
+        for child in [self.rebuild_range(lo, mid), self.rebuild_range(mid + 1, hi)]
+            .into_iter()
+            .flatten()
+        {
+            if child > max_end {
+                max_end = child;
+            }
+        }
+        self.max_ends[mid] = max_end;
+        Some(max_end)
+    }
+
+    /// Visits spans which end after `after` and whose start satisfies `start_pred`.
+    /// `start_pred` must be monotone (true for a prefix of starts).
+    fn visit<'a>(
+        &'a self,
+        lo: usize,
+        hi: usize,
+        after: U,
+        start_pred: &impl Fn(&U) -> bool,
+        f: &mut impl FnMut(U, U, &'a V),
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_ends[mid] <= after {
+            return;
+        }
+
+        self.visit(lo, mid, after, start_pred, f);
+        if start_pred(&self.starts[mid]) {
+            if self.ends[mid] > after {
+                f(self.starts[mid], self.ends[mid], &self.items[mid]);
+            }
+            self.visit(mid + 1, hi, after, start_pred, f);
+        }
+    }
+}
+
+impl<U, V> Default for SpanTimeline<U, V>
+where
+    U: TimeUnit,
+{
+    fn default() -> Self {
+        SpanTimeline::new()
+    }
+}
+
+impl<U, V> Extend<(U, U, V)> for SpanTimeline<U, V>
+where
+    U: TimeUnit,
+{
+    /// Inserts spans, rebuilding the index once at last.
+    fn extend<T: IntoIterator<Item = (U, U, V)>>(&mut self, iter: T) {
+        for (start, end, item) in iter {
+            self.insert_unindexed(start, end, item);
+        }
+        self.rebuild_index();
+    }
+}
+
+impl<U, V> FromIterator<(U, U, V)> for SpanTimeline<U, V>
+where
+    U: TimeUnit,
+{
+    fn from_iter<T: IntoIterator<Item = (U, U, V)>>(iter: T) -> Self {
+        let mut stl = SpanTimeline::new();
+        stl.extend(iter);
+        stl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanTimeline;
+    use crate::instant;
+
+    #[test]
+    fn span_queries_work() {
+        let stl: SpanTimeline<_, _> = vec![
+            (0, 10, 'a'),
+            (2, 4, 'b'),
+            (3, 8, 'c'),
+            (5, 6, 'd'),
+            (9, 12, 'e'),
+            (12, 13, 'f'),
+        ]
+        .into_iter()
+        .collect();
+
+        let items =
+            |v: Vec<(i32, i32, &char)>| v.into_iter().map(|(_, _, c)| *c).collect::<String>();
+        assert_eq!(items(stl.active_at(0)), "a");
+        assert_eq!(items(stl.active_at(3)), "abc");
+        assert_eq!(items(stl.active_at(4)), "ac");
+        assert_eq!(items(stl.active_at(10)), "e");
+        assert_eq!(items(stl.active_at(12)), "f");
+        assert_eq!(items(stl.active_at(13)), "");
+
+        assert_eq!(items(stl.overlapping(4, 5)), "ac");
+        assert_eq!(items(stl.overlapping(4, 6)), "acd");
+        assert_eq!(items(stl.overlapping(10, 12)), "e");
+        assert_eq!(items(stl.overlapping(-5, 100)), "abcdef");
+    }
+
+    #[test]
+    fn span_insertion_works() {
+        let mut stl = SpanTimeline::new();
+        stl.insert(instant![4:0/1], instant![6:0/1], 2);
+        stl.insert(instant![0:0/1], instant![8:1/2], 1);
+        stl.insert(instant![5:0/1], instant![5:1/4], 3);
+
+        let active: Vec<_> = stl
+            .active_at(instant![5:1/8])
+            .into_iter()
+            .map(|(_, _, v)| *v)
+            .collect();
+        assert_eq!(active, vec![1, 2, 3]);
+        let active: Vec<_> = stl
+            .active_at(instant![7:0/1])
+            .into_iter()
+            .map(|(_, _, v)| *v)
+            .collect();
+        assert_eq!(active, vec![1]);
+
+        stl.extend([
+            (instant![7:1/2], instant![9:0/1], 5),
+            (instant![2:0/1], instant![7:3/4], 4),
+        ]);
+        let active: Vec<_> = stl
+            .active_at(instant![7:5/8])
+            .into_iter()
+            .map(|(_, _, v)| *v)
+            .collect();
+        assert_eq!(active, vec![1, 4, 5], "extended spans are indexed");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid span")]
+    fn empty_span_panicks() {
+        let mut stl = SpanTimeline::new();
+        stl.insert(1, 1, ());
+    }
+}
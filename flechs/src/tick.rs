@@ -0,0 +1,200 @@
+//! Contains tick-based time unit (MIDI, bmson, etc.).
+
+use crate::{
+    preintegral::Preintegral,
+    snap::Rounding,
+    time::{Instant, TimeUnit},
+    timeline::Timeline,
+    util::signed_ratio,
+    value::Beat,
+};
+
+use std::cmp::Ordering;
+
+use num::rational::Ratio;
+
+/// Represents a time in integer ticks with resolution (ticks per beat, PPQ).
+/// Ticks of different resolutions are compared by their actual position.
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    ticks: usize,
+    resolution: usize,
+}
+
+impl TimeUnit for Tick {}
+
+impl Tick {
+    /// Creates new tick. Panics if resolution is zero.
+    pub const fn new(ticks: usize, resolution: usize) -> Tick {
+        if resolution == 0 {
+            panic!("invalid resolution");
+        }
+        Tick { ticks, resolution }
+    }
+
+    pub const fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub const fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    /// Returns position in beats.
+    pub fn to_beats(&self) -> Ratio<usize> {
+        Ratio::new(self.ticks, self.resolution)
+    }
+}
+
+impl PartialEq for Tick {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_beats() == other.to_beats()
+    }
+}
+
+impl Eq for Tick {}
+
+impl PartialOrd for Tick {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Tick {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_beats().cmp(&other.to_beats())
+    }
+}
+
+/// Represents the result of conversion into ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickRounding {
+    pub tick: Tick,
+
+    /// Exact position minus rounded one, in ticks.
+    pub error: Ratio<isize>,
+}
+
+impl TickRounding {
+    /// Returns whether the conversion is lossless.
+    pub fn is_exact(&self) -> bool {
+        *self.error.numer() == 0
+    }
+}
+
+/// Converts between `Tick` and `Instant` through beat timeline.
+#[derive(Debug, Clone)]
+pub struct TickConverter {
+    beats: Preintegral<usize, Beat>,
+    resolution: usize,
+}
+
+impl TickConverter {
+    /// Creates new converter. Beat timeline must be zero-aligned.
+    pub fn new(beats: Timeline<usize, Beat>, resolution: usize) -> TickConverter {
+        assert!(resolution > 0, "invalid resolution");
+        TickConverter {
+            beats: Preintegral::new(beats),
+            resolution,
+        }
+    }
+
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    /// Returns beats from zero at specified instant.
+    pub fn beats_at(&self, time: Instant) -> Ratio<usize> {
+        let Beat(beat) = *self.beats.item_at(time.measure());
+        self.beats.fetch(time.measure()) + time.submeasure() * beat
+    }
+
+    /// Returns instant at specified beats from zero.
+    pub fn instant_at_beats(&self, beats: Ratio<usize>) -> Instant {
+        let (start, Beat(beat), base) = self.beats.section_by_value(&beats);
+        Instant::from_measures((beats - base) / beat + start)
+    }
+
+    /// Converts tick into instant. Always exact.
+    pub fn to_instant(&self, tick: Tick) -> Instant {
+        self.instant_at_beats(tick.to_beats())
+    }
+
+    /// Converts instant into tick of this resolution, reporting rounding error.
+    pub fn to_tick(&self, time: Instant, rounding: Rounding) -> TickRounding {
+        let exact = self.beats_at(time) * self.resolution;
+        let floor = exact.floor();
+        let ceil = exact.ceil();
+        let rounded = match rounding {
+            Rounding::Floor => floor,
+            Rounding::Ceil => ceil,
+            Rounding::Nearest if exact - floor < ceil - exact => floor,
+            Rounding::Nearest => ceil,
+        };
+
+        TickRounding {
+            tick: Tick::new(rounded.to_integer(), self.resolution),
+            error: signed_ratio(exact) - signed_ratio(rounded),
+        }
+    }
+
+    /// Converts instant into tick, or `None` if the resolution cannot represent it.
+    pub fn to_tick_exact(&self, time: Instant) -> Option<Tick> {
+        let rounding = self.to_tick(time, Rounding::Floor);
+        rounding.is_exact().then_some(rounding.tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tick, TickConverter};
+    use crate::{instant, snap::Rounding, timeline, value::Beat};
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn tick_ordering_works() {
+        assert_eq!(Tick::new(240, 480), Tick::new(96, 192));
+        assert!(Tick::new(241, 480) > Tick::new(96, 192));
+    }
+
+    #[test]
+    fn tick_conversion_works() {
+        let beats = timeline! {
+            [0]: Beat(Ratio::new(4, 1)),
+            [2]: Beat(Ratio::new(7, 2)),
+        };
+        let converter = TickConverter::new(beats, 480);
+
+        assert_eq!(
+            converter.to_instant(Tick::new(480 * 8, 480)),
+            instant![2:0/1]
+        );
+        assert_eq!(
+            converter.to_instant(Tick::new(480 * 8 + 240, 480)),
+            instant![2:1/7]
+        );
+        assert_eq!(
+            converter.to_instant(Tick::new(480 * 8 + 240 * 7, 480)),
+            instant![3:0/1]
+        );
+
+        for time in [
+            instant![0:0/1],
+            instant![1:3/16],
+            instant![2:1/7],
+            instant![2:5/14],
+            instant![5:1/21],
+        ] {
+            let tick = converter.to_tick_exact(time).expect("must be exact");
+            assert_eq!(converter.to_instant(tick), time, "round trip is lossless");
+        }
+
+        // 1/7 beat is not representable in 480 PPQ
+        let rounding = converter.to_tick(instant![0:1/28], Rounding::Nearest);
+        assert!(!rounding.is_exact());
+        assert_eq!(rounding.tick, Tick::new(69, 480));
+        assert_eq!(rounding.error, Ratio::new(-3, 7));
+        assert_eq!(converter.to_tick_exact(instant![0:1/28]), None);
+    }
+}
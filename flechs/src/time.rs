@@ -1,5 +1,9 @@
 //! Basic time structs.
 
+use crate::util::{signed_ratio, unsigned_ratio};
+
+use std::ops::{Add, Sub};
+
 use num::{rational::Ratio, Integer, Zero};
 use thiserror::Error as ThisError;
 
@@ -9,6 +13,8 @@ pub trait TimeUnit: PartialOrd + Copy {}
 impl<N> TimeUnit for N where N: Copy + Integer + Zero {}
 
 /// Represents a paticular instant time in chart/score.
+/// Elapsed seconds at an instant are computed by `ChartClock<N>` in the integer type of choice,
+/// but the instant itself is always `Ratio<usize>` and its arithmetic is unchecked.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Instant {
     /// 0-based measure number.
@@ -68,6 +74,212 @@ impl Instant {
     pub const fn submeasure(&self) -> Ratio<usize> {
         self.submeasure
     }
+
+    /// Returns this instant as a measure count from zero.
+    pub fn to_measures(&self) -> Ratio<usize> {
+        self.submeasure + self.measure
+    }
+
+    /// Returns the start of next measure, or itself if it is already at measure start.
+    pub fn ceil_measure(&self) -> Instant {
+        if self.submeasure == Ratio::zero() {
+            *self
+        } else {
+            Instant::new_parts(self.measure + 1, 0, 1)
+        }
+    }
+
+    /// Creates new instant from a measure count from zero.
+    pub fn from_measures(measures: Ratio<usize>) -> Instant {
+        Instant {
+            measure: measures.to_integer(),
+            submeasure: measures.fract(),
+        }
+    }
+}
+
+/// Represents a paticular instant time which may be before measure 0 (lead-in, pre-roll).
+/// `-1:3/4` is a quarter measure before measure 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SignedInstant {
+    /// 0-based measure number, negative before the first measure.
+    measure: isize,
+
+    /// time in measure.
+    submeasure: Ratio<usize>,
+}
+
+impl TimeUnit for SignedInstant {}
+
+impl SignedInstant {
+    /// Returns zero.
+    pub const fn zero() -> SignedInstant {
+        SignedInstant {
+            measure: 0,
+            submeasure: Ratio::new_raw(0, 1),
+        }
+    }
+
+    /// Creates new instant.
+    pub fn new(measure: isize, submeasure: Ratio<usize>) -> Result<SignedInstant, InstantError> {
+        if submeasure >= Ratio::new(1, 1) {
+            Err(InstantError::OverSubmeasure(submeasure))
+        } else {
+            Ok(SignedInstant {
+                measure,
+                submeasure,
+            })
+        }
+    }
+
+    /// Creates new instant with parts.
+    pub const fn new_parts(measure: isize, sub_numer: usize, sub_denom: usize) -> SignedInstant {
+        if sub_denom == 0 || sub_numer >= sub_denom {
+            panic!("invalid submeasure");
+        } else {
+            SignedInstant {
+                measure,
+                submeasure: Ratio::new_raw(sub_numer, sub_denom),
+            }
+        }
+    }
+
+    pub const fn measure(&self) -> isize {
+        self.measure
+    }
+
+    pub const fn submeasure(&self) -> Ratio<usize> {
+        self.submeasure
+    }
+
+    /// Returns this instant as a measure count from zero.
+    pub fn to_measures(&self) -> Ratio<isize> {
+        signed_ratio(self.submeasure) + self.measure
+    }
+
+    /// Creates new instant from a measure count from zero.
+    pub fn from_measures(measures: Ratio<isize>) -> SignedInstant {
+        let measure = measures.floor();
+        SignedInstant {
+            measure: measure.to_integer(),
+            submeasure: unsigned_ratio(measures - measure),
+        }
+    }
+
+    /// Converts into unsigned instant, or `None` if before measure 0.
+    pub fn to_instant(&self) -> Option<Instant> {
+        let measure = usize::try_from(self.measure).ok()?;
+        Some(Instant {
+            measure,
+            submeasure: self.submeasure,
+        })
+    }
+}
+
+impl From<Instant> for SignedInstant {
+    fn from(value: Instant) -> SignedInstant {
+        SignedInstant {
+            measure: isize::try_from(value.measure).expect("must be representable"),
+            submeasure: value.submeasure,
+        }
+    }
+}
+
+/// Represents exact wall-clock seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Seconds(pub Ratio<usize>);
+
+impl TimeUnit for Seconds {}
+
+impl Seconds {
+    /// Returns zero.
+    pub const fn zero() -> Seconds {
+        Seconds(Ratio::new_raw(0, 1))
+    }
+
+    /// Converts into microseconds, truncating sub-microsecond part.
+    pub fn to_micros(&self) -> Micros {
+        Micros((self.0 * 1_000_000).to_integer() as u64)
+    }
+}
+
+impl Add for Seconds {
+    type Output = Seconds;
+
+    fn add(self, rhs: Seconds) -> Seconds {
+        Seconds(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Seconds {
+    type Output = Seconds;
+
+    fn sub(self, rhs: Seconds) -> Seconds {
+        Seconds(self.0 - rhs.0)
+    }
+}
+
+impl From<Micros> for Seconds {
+    fn from(value: Micros) -> Seconds {
+        Seconds(Ratio::new(value.0 as usize, 1_000_000))
+    }
+}
+
+/// Represents exact wall-clock seconds which may be negative, e.g. playback seconds of input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SignedSeconds(pub Ratio<isize>);
+
+impl TimeUnit for SignedSeconds {}
+
+impl SignedSeconds {
+    /// Returns zero.
+    pub const fn zero() -> SignedSeconds {
+        SignedSeconds(Ratio::new_raw(0, 1))
+    }
+}
+
+impl Add for SignedSeconds {
+    type Output = SignedSeconds;
+
+    fn add(self, rhs: SignedSeconds) -> SignedSeconds {
+        SignedSeconds(self.0 + rhs.0)
+    }
+}
+
+impl Sub for SignedSeconds {
+    type Output = SignedSeconds;
+
+    fn sub(self, rhs: SignedSeconds) -> SignedSeconds {
+        SignedSeconds(self.0 - rhs.0)
+    }
+}
+
+impl From<Seconds> for SignedSeconds {
+    fn from(value: Seconds) -> SignedSeconds {
+        SignedSeconds(signed_ratio(value.0))
+    }
+}
+
+/// Represents fixed-point wall-clock time in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Micros(pub u64);
+
+impl TimeUnit for Micros {}
+
+impl Add for Micros {
+    type Output = Micros;
+
+    fn add(self, rhs: Micros) -> Micros {
+        Micros(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Micros {
+    type Output = Micros;
+
+    fn sub(self, rhs: Micros) -> Micros {
+        Micros(self.0 - rhs.0)
+    }
 }
 
 /// Constructs an `Instant` in const context.
@@ -78,9 +290,18 @@ macro_rules! instant {
     };
 }
 
+/// Constructs a `SignedInstant` in const context.
+#[macro_export]
+macro_rules! signed_instant {
+    [$m:literal : $sn:literal / $sd:literal] => {
+        $crate::time::SignedInstant::new_parts($m, $sn, $sd)
+    };
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Instant, InstantError};
+    use super::{Instant, InstantError, Micros, Seconds, SignedInstant, SignedSeconds};
+    use crate::timeline::Timeline;
 
     use num::rational::Ratio;
 
@@ -111,6 +332,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn instant_measures_conversion_works() {
+        assert_eq!(instant![3:1/4].to_measures(), Ratio::new(13, 4));
+        assert_eq!(Instant::from_measures(Ratio::new(13, 4)), instant![3:1/4]);
+        assert_eq!(Instant::from_measures(Ratio::new(2, 1)), instant![2:0/1]);
+        assert_eq!(instant![3:1/4].ceil_measure(), instant![4:0/1]);
+        assert_eq!(instant![3:0/1].ceil_measure(), instant![3:0/1]);
+    }
+
+    #[test]
+    fn signed_instant_works() {
+        let lead_in = signed_instant![-1:3/4];
+        assert!(lead_in < SignedInstant::zero(), "lead-in is before zero");
+        assert!(signed_instant![-2:1/2] < lead_in);
+        assert!(lead_in < signed_instant![0:1/4]);
+
+        assert_eq!(lead_in.to_measures(), Ratio::new(-1, 4));
+        assert_eq!(SignedInstant::from_measures(Ratio::new(-1, 4)), lead_in);
+        assert_eq!(
+            SignedInstant::from_measures(Ratio::new(-3, 1)),
+            signed_instant![-3:0/1]
+        );
+
+        assert_eq!(lead_in.to_instant(), None);
+        assert_eq!(
+            SignedInstant::from(instant![2:1/3]).to_instant(),
+            Some(instant![2:1/3])
+        );
+    }
+
+    #[test]
+    fn wall_clock_units_work() {
+        let seconds = Seconds(Ratio::new(7, 3));
+        assert_eq!(seconds.to_micros(), Micros(2_333_333), "truncated");
+        assert_eq!(Seconds::from(Micros(1_500_000)), Seconds(Ratio::new(3, 2)));
+        assert_eq!(
+            seconds - Seconds(Ratio::new(1, 3)),
+            Seconds(Ratio::new(2, 1))
+        );
+        assert_eq!(Micros(250) + Micros(750), Micros(1000));
+
+        let lead_in = SignedSeconds(Ratio::new(-1, 2));
+        assert!(lead_in < SignedSeconds::zero());
+        assert_eq!(
+            SignedSeconds::from(seconds) + lead_in,
+            SignedSeconds(Ratio::new(11, 6))
+        );
+        let log: Timeline<SignedSeconds, usize> = [(lead_in, 0), (SignedSeconds::zero(), 1)]
+            .into_iter()
+            .collect();
+        assert_eq!(log.times().next(), Some(lead_in), "keyed in timeline");
+    }
+
     #[test]
     fn instant_macro_works() {
         assert_eq!(instant![0:0/1].measure, 0, "Instant macro works");
@@ -18,6 +18,9 @@ pub enum TimelineError {
     NotZeroAligned,
 }
 
+/// Timeline of optional pairs produced by `Timeline::merge`.
+pub type MergedTimeline<U, V, W> = Timeline<U, (Option<V>, Option<W>)>;
+
 /// Represents a item timeline.
 #[derive(Debug, Clone)]
 pub struct Timeline<U, V> {
@@ -47,6 +50,21 @@ where
         self.items.iter()
     }
 
+    /// Returns iterator of pairs.
+    pub fn pairs(&self) -> impl Iterator<Item = (U, &V)> {
+        zip(self.times.iter().copied(), self.items.iter())
+    }
+
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Returns whether this timeline has no items.
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
     /// Consumes itself and returns pair iterator.
     pub fn into_pairs(self) -> impl Iterator<Item = (U, V)> {
         zip(self.times, self.items)
@@ -73,6 +91,34 @@ where
         self.items.insert(target_index, item);
     }
 
+    /// Gets the first item exactly at specified time.
+    pub fn get(&self, time: U) -> Option<&V> {
+        let left = lower_bound(&self.times, &time);
+        match self.times.get(left) {
+            Some(t) if *t == time => Some(&self.items[left]),
+            _ => None,
+        }
+    }
+
+    /// Returns iterator of pairs within `[start, end)`.
+    pub fn range(&self, start: U, end: U) -> impl Iterator<Item = (U, &V)> {
+        let left = lower_bound(&self.times, &start);
+        let right = lower_bound(&self.times, &end).max(left);
+        zip(
+            self.times[left..right].iter().copied(),
+            self.items[left..right].iter(),
+        )
+    }
+
+    /// Returns iterator of pairs at or after `start`.
+    pub fn range_from(&self, start: U) -> impl Iterator<Item = (U, &V)> {
+        let left = lower_bound(&self.times, &start);
+        zip(
+            self.times[left..].iter().copied(),
+            self.items[left..].iter(),
+        )
+    }
+
     /// Gets latest item.
     pub fn latest_item(&self, time: U) -> Option<&V> {
         let left = upper_bound(&self.times, &time);
@@ -112,10 +158,7 @@ where
     }
 
     /// Merges two timeline into one timeline of tuples.
-    pub fn merge<W>(
-        self,
-        right: Timeline<U, W>,
-    ) -> Result<Timeline<U, (Option<V>, Option<W>)>, TimelineError> {
+    pub fn merge<W>(self, right: Timeline<U, W>) -> Result<MergedTimeline<U, V, W>, TimelineError> {
         if self.has_duplicate_times() || right.has_duplicate_times() {
             return Err(TimelineError::HasDuplicateTimes);
         }
@@ -163,6 +206,15 @@ where
     }
 }
 
+impl<U, V> Default for Timeline<U, V>
+where
+    U: TimeUnit,
+{
+    fn default() -> Self {
+        Timeline::new()
+    }
+}
+
 impl<U, V> FromIterator<(U, V)> for Timeline<U, V>
 where
     U: TimeUnit,
@@ -231,6 +283,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn timeline_range_works() {
+        let tl = timeline! {
+            [0:0/1]: 'a',
+            [0:1/2]: 'b',
+            [1:0/1]: 'c',
+            [2:3/4]: 'd',
+        };
+
+        let items: String = tl
+            .range(instant![0:1/2], instant![2:3/4])
+            .map(|(_, c)| *c)
+            .collect();
+        assert_eq!(items, "bc", "range is half-open");
+        let items: String = tl.range_from(instant![0:1/4]).map(|(_, c)| *c).collect();
+        assert_eq!(items, "bcd", "range_from works");
+        assert_eq!(tl.range(instant![2:0/1], instant![1:0/1]).count(), 0);
+    }
+
     #[test]
     fn advanced_timeline_works() {
         // TODO: write test
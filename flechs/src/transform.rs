@@ -0,0 +1,283 @@
+//! Contains lane option transforms.
+//!
+//! Transforms move notes among specified key lanes only,
+//! so scratch lanes (and any other lane left out) stay fixed.
+//! Hold notes always move as a whole, and occupy their lane over `[start, end)`.
+
+use crate::{
+    chart::Chart,
+    note::{LaneId, Note},
+    time::Instant,
+    util::SplitMix64,
+};
+
+use std::collections::HashMap;
+
+use thiserror::Error as ThisError;
+
+/// Represents an error about lane transforms.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum TransformError {
+    /// The note of the lane has no lane to move into at the time.
+    #[error("note of lane {0:?} at {1:?} cannot be placed")]
+    Unplaceable(LaneId, Instant),
+
+    /// Sides of double play have different numbers of lanes.
+    #[error("sides have {left} and {right} lanes")]
+    AsymmetricSides { left: usize, right: usize },
+}
+
+/// Represents a lane option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaneOption {
+    Off,
+    Mirror,
+
+    /// Random permutation of lanes.
+    Random,
+
+    /// Random rotation of lanes, possibly mirrored.
+    RRandom,
+
+    /// Random lane for each note, avoiding jacks where possible.
+    SRandom,
+}
+
+/// Applies lane option on key lanes, seeded.
+pub fn transform(
+    chart: &Chart,
+    option: LaneOption,
+    keys: &[LaneId],
+    seed: u64,
+) -> Result<Chart, TransformError> {
+    let mut rng = SplitMix64::new(seed);
+    let mut targets = keys.to_vec();
+    match option {
+        LaneOption::Off => {}
+        LaneOption::Mirror => targets.reverse(),
+        LaneOption::Random => rng.shuffle(&mut targets),
+        LaneOption::RRandom => {
+            if rng.below(2) == 1 {
+                targets.reverse();
+            }
+            let len = targets.len();
+            targets.rotate_left(if len > 1 { 1 + rng.below(len - 1) } else { 0 });
+        }
+        LaneOption::SRandom => return s_random(chart, keys, &mut rng),
+    }
+    permute(chart, keys, &targets)
+}
+
+/// Swaps two sides of double play, lane by lane.
+/// Sides must have the same number of lanes.
+pub fn flip(chart: &Chart, left: &[LaneId], right: &[LaneId]) -> Result<Chart, TransformError> {
+    if left.len() != right.len() {
+        return Err(TransformError::AsymmetricSides {
+            left: left.len(),
+            right: right.len(),
+        });
+    }
+    let sources: Vec<_> = left.iter().chain(right.iter()).copied().collect();
+    let targets: Vec<_> = right.iter().chain(left.iter()).copied().collect();
+    permute(chart, &sources, &targets)
+}
+
+/// Moves notes of `sources[i]` into `targets[i]`.
+fn permute(chart: &Chart, sources: &[LaneId], targets: &[LaneId]) -> Result<Chart, TransformError> {
+    rebuild(chart, |_, note| {
+        sources
+            .iter()
+            .position(|&lane| lane == note.lane)
+            .map_or(note.lane, |i| targets[i])
+    })
+}
+
+/// Places each note of key lanes in chronological order.
+/// Prefers lanes free of holds and of notes of the previous chord.
+fn s_random(chart: &Chart, keys: &[LaneId], rng: &mut SplitMix64) -> Result<Chart, TransformError> {
+    let mut notes: Vec<_> = chart
+        .notes()
+        .filter(|(_, note)| keys.contains(&note.lane))
+        .map(|(time, note)| (time, *note))
+        .collect();
+    notes.sort_by_key(|(time, _)| *time);
+
+    // per key; the end of the hold, and the time of the last note
+    let mut hold_ends: Vec<Option<Instant>> = vec![None; keys.len()];
+    let mut last_times: Vec<Option<Instant>> = vec![None; keys.len()];
+    let mut previous: Option<Instant> = None;
+    let mut current: Option<Instant> = None;
+    let mut placed = HashMap::new();
+    for (time, note) in notes {
+        if current != Some(time) {
+            previous = current;
+            current = Some(time);
+        }
+
+        let free: Vec<_> = (0..keys.len())
+            .filter(|&k| hold_ends[k].is_none_or(|end| end <= time))
+            .filter(|&k| last_times[k] != Some(time))
+            .collect();
+        let unjacked: Vec<_> = free
+            .iter()
+            .copied()
+            .filter(|&k| previous.is_none() || last_times[k] != previous)
+            .collect();
+        let candidates = if unjacked.is_empty() { free } else { unjacked };
+        if candidates.is_empty() {
+            return Err(TransformError::Unplaceable(note.lane, time));
+        }
+        let k = candidates[rng.below(candidates.len())];
+
+        last_times[k] = Some(time);
+        hold_ends[k] = note.hold_end();
+        placed.insert((time, note.lane), keys[k]);
+    }
+
+    rebuild(chart, |time, note| {
+        placed.get(&(time, note.lane)).copied().unwrap_or(note.lane)
+    })
+}
+
+/// Builds chart moving each note into specified lane.
+fn rebuild(
+    chart: &Chart,
+    mut place: impl FnMut(Instant, &Note) -> LaneId,
+) -> Result<Chart, TransformError> {
+    let mut result = Chart::with_lanes(chart.rhythm().clone(), chart.lane_kinds().to_vec());
+    for (time, note) in chart.notes() {
+        let lane = place(time, note);
+        result
+            .insert_note(time, Note { lane, ..*note })
+            .map_err(|_| TransformError::Unplaceable(note.lane, time))?;
+    }
+    for (time, keysound) in chart.bgm().pairs() {
+        result.insert_bgm(time, *keysound);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flip, transform, LaneOption, TransformError};
+    use crate::{
+        chart::Chart,
+        fixture::{self, hold},
+        instant,
+        keymode::KeyMode,
+        note::{LaneId, NoteKind},
+        time::Instant,
+    };
+
+    /// Returns 7K chart of the notes.
+    fn chart(notes: &[(Instant, usize, NoteKind)]) -> Chart {
+        fixture::chart_with(fixture::rhythm(120), KeyMode::K7.lane_kinds(), notes).0
+    }
+
+    /// Returns 7K chart of a scratch, a hold and 16 rows of 2 notes.
+    fn rows() -> Chart {
+        let mut notes = vec![
+            (instant![0:0/1], 0, NoteKind::Tap),
+            (instant![0:0/1], 1, hold(instant![1:0/1])),
+        ];
+        for i in 0..16 {
+            let time = Instant::new_parts(i / 4, i % 4, 4);
+            notes.push((time, 2 + i % 3, NoteKind::Tap));
+            notes.push((time, 7, NoteKind::Tap));
+        }
+        chart(&notes)
+    }
+
+    fn lanes_at(chart: &Chart, time: Instant) -> Vec<usize> {
+        chart
+            .notes()
+            .filter(|(t, _)| *t == time)
+            .map(|(_, note)| note.lane.0)
+            .collect()
+    }
+
+    #[test]
+    fn lane_transforms_work() {
+        let chart = rows();
+        let keys: Vec<_> = (1..8).map(LaneId).collect();
+
+        let mirror = transform(&chart, LaneOption::Mirror, &keys, 0).unwrap();
+        assert_eq!(lanes_at(&mirror, instant![0:0/1]), vec![0, 1, 6, 7]);
+        assert_eq!(
+            mirror
+                .lane(LaneId(7))
+                .unwrap()
+                .get(instant![0:0/1])
+                .unwrap()
+                .kind,
+            NoteKind::Hold {
+                end: instant![1:0/1]
+            }
+        );
+
+        for option in [LaneOption::Random, LaneOption::RRandom, LaneOption::SRandom] {
+            let a = transform(&chart, option, &keys, 3).unwrap();
+            let b = transform(&chart, option, &keys, 3).unwrap();
+            assert_eq!(a.stable_hash(), b.stable_hash(), "reproducible");
+            assert_eq!(a.note_count(), chart.note_count());
+            assert_eq!(a.lane(LaneId(0)).unwrap().len(), 1, "scratch lane is fixed");
+            let holds: Vec<_> = a.notes().filter_map(|(_, n)| n.hold_end()).collect();
+            assert_eq!(holds, vec![instant![1:0/1]], "hold moves as a whole");
+        }
+
+        // S-random avoids jacks; 2 notes per row, 7 keys
+        let s_random = transform(&chart, LaneOption::SRandom, &keys, 11).unwrap();
+        for i in 1..16 {
+            let previous = lanes_at(&s_random, Instant::new_parts((i - 1) / 4, (i - 1) % 4, 4));
+            let current = lanes_at(&s_random, Instant::new_parts(i / 4, i % 4, 4));
+            assert!(current.iter().all(|lane| !previous.contains(lane)));
+        }
+        // hold on its lane blocks other notes until its end
+        let hold_lane = s_random
+            .notes()
+            .find(|(_, n)| n.hold_end().is_some())
+            .unwrap()
+            .1
+            .lane;
+        let during_hold = s_random
+            .lane(hold_lane)
+            .unwrap()
+            .range(instant![0:1/4], instant![1:0/1])
+            .count();
+        assert_eq!(during_hold, 0);
+
+        let left: Vec<_> = (1..4).map(LaneId).collect();
+        let right: Vec<_> = (5..8).map(LaneId).collect();
+        let flipped = flip(&chart, &left, &right).unwrap();
+        assert_eq!(lanes_at(&flipped, instant![0:0/1]), vec![0, 3, 5, 6]);
+    }
+
+    #[test]
+    fn hold_end_frees_its_lane() {
+        let mut chart = chart(&[
+            (instant![0:0/1], 1, hold(instant![1:0/1])),
+            (instant![1:0/1], 1, NoteKind::Tap),
+        ]);
+
+        // the only key takes the tap right at the end of the hold
+        let s_random = transform(&chart, LaneOption::SRandom, &[LaneId(1)], 0).unwrap();
+        assert_eq!(lanes_at(&s_random, instant![1:0/1]), vec![1]);
+
+        // a chord wider than free keys during the hold
+        fixture::insert_notes(
+            &mut chart,
+            &[
+                (instant![0:1/2], 1, NoteKind::Tap),
+                (instant![0:1/2], 2, NoteKind::Tap),
+            ],
+        );
+        assert_eq!(
+            transform(&chart, LaneOption::SRandom, &[LaneId(1), LaneId(2)], 0).unwrap_err(),
+            TransformError::Unplaceable(LaneId(2), instant![0:1/2])
+        );
+        assert_eq!(
+            flip(&chart, &[LaneId(1)], &[]).unwrap_err(),
+            TransformError::AsymmetricSides { left: 1, right: 0 }
+        );
+    }
+}
@@ -1,7 +1,54 @@
+use num::{rational::Ratio, FromPrimitive, Integer};
+
+use std::hash::Hasher;
+
+/// Converts unsigned rational into signed one. Panics if it exceeds `isize`.
+pub fn signed_ratio(value: Ratio<usize>) -> Ratio<isize> {
+    checked_signed_ratio(value).expect("must be representable")
+}
+
+/// Converts unsigned rational into signed one, or returns `None` if it exceeds `isize`.
+pub fn checked_signed_ratio(value: Ratio<usize>) -> Option<Ratio<isize>> {
+    checked_cast_ratio(value)
+}
+
+/// Converts unsigned rational into one of another integer type.
+/// Panics if the type cannot represent it.
+pub fn cast_ratio<N>(value: Ratio<usize>) -> Ratio<N>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    checked_cast_ratio(value).expect("must be representable")
+}
+
+/// Converts unsigned rational into one of another integer type,
+/// or returns `None` if the type cannot represent it.
+pub fn checked_cast_ratio<N>(value: Ratio<usize>) -> Option<Ratio<N>>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    let numer = N::from_usize(*value.numer())?;
+    let denom = N::from_usize(*value.denom())?;
+    Some(Ratio::new_raw(numer, denom))
+}
+
+/// Converts signed rational into unsigned one. Panics if negative.
+pub fn unsigned_ratio(value: Ratio<isize>) -> Ratio<usize> {
+    checked_unsigned_ratio(value).expect("negative rational")
+}
+
+/// Converts signed rational into unsigned one, or returns `None` if negative.
+pub fn checked_unsigned_ratio(value: Ratio<isize>) -> Option<Ratio<usize>> {
+    let value = value.reduced();
+    let numer = usize::try_from(*value.numer()).ok()?;
+    let denom = usize::try_from(*value.denom()).ok()?;
+    Some(Ratio::new_raw(numer, denom))
+}
+
 /// Searches lower bound index for specified time.
 pub fn lower_bound<T: PartialOrd>(target: &[T], item: &T) -> usize {
     let mut search_range = 0..(target.len());
-    while search_range.len() > 0 {
+    while !search_range.is_empty() {
         let mid = search_range.len() / 2 + search_range.start;
         search_range = if item <= &target[mid] {
             (search_range.start)..mid
@@ -16,7 +63,7 @@ pub fn lower_bound<T: PartialOrd>(target: &[T], item: &T) -> usize {
 /// Searches upper bound index for specified time.
 pub fn upper_bound<T: PartialOrd>(target: &[T], item: &T) -> usize {
     let mut search_range = 0..(target.len());
-    while search_range.len() > 0 {
+    while !search_range.is_empty() {
         let mid = search_range.len() / 2 + search_range.start;
         search_range = if item < &target[mid] {
             (search_range.start)..mid
@@ -27,9 +74,132 @@ pub fn upper_bound<T: PartialOrd>(target: &[T], item: &T) -> usize {
     search_range.start
 }
 
+/// 64-bit FNV-1a hasher. Unlike `DefaultHasher`, stable across platforms and releases;
+/// integers are written as little-endian 64-bit.
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(u64::from(i));
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(u64::from(i));
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(u64::from(i));
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.write_i64(i64::from(i));
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_i64(i64::from(i));
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_i64(i64::from(i));
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+/// Seeded pseudo random generator (SplitMix64), reproducible across platforms.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub const fn new(seed: u64) -> SplitMix64 {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns uniform integer less than `bound`. Panics if zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "invalid bound");
+        let bound = bound as u64;
+        // rejects the last incomplete cycle to stay uniform
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// Shuffles items uniformly (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{lower_bound, upper_bound};
+    use super::{checked_signed_ratio, checked_unsigned_ratio, lower_bound, upper_bound};
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn checked_ratio_casts_work() {
+        assert_eq!(
+            checked_signed_ratio(Ratio::new(3, 4)),
+            Some(Ratio::new(3, 4))
+        );
+        assert_eq!(
+            checked_signed_ratio(Ratio::new_raw(usize::MAX, 1)),
+            None,
+            "fails instead of wrapping"
+        );
+        assert_eq!(
+            checked_unsigned_ratio(Ratio::new(-6, -8)),
+            Some(Ratio::new(3, 4))
+        );
+        assert_eq!(checked_unsigned_ratio(Ratio::new(-3, 4)), None, "negative");
+    }
 
     #[test]
     fn bound_functions_work() {
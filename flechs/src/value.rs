@@ -1,22 +1,37 @@
 //! Contains various value types.
+//!
+//! `Beat`, `Tempo` and `RhythmChange` are generic over the integer type of their rationals.
+//! `usize` is used by default; `num::BigInt` never overflows.
+//!
+//! `Stop`, the integration of `Tempo` over `Seconds` and `Micros`, and of `RhythmChange`
+//! over `SignedInstant` are still `usize` only. Of these, only the last detects overflow,
+//! through `Preintegral::try_new`. Use `ChartClock<N>` where elapsed seconds may overflow.
 
-use num::{rational::Ratio, Zero};
+use num::{
+    rational::Ratio, CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromPrimitive, Integer, Zero,
+};
 
 use crate::{
-    preintegral::Integrable,
-    time::Instant,
+    preintegral::{CheckedIntegrable, Integrable},
+    time::{Instant, Micros, Seconds, SignedInstant, TimeUnit},
     timeline::{Timeline, TimelineError},
+    util::{cast_ratio, checked_unsigned_ratio, signed_ratio, unsigned_ratio},
 };
 
 /// Represents beat event.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Beat(pub Ratio<usize>);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Beat<N = usize>(pub Ratio<N>)
+where
+    N: Clone + Integer;
 
-impl Integrable<usize> for Beat {
-    type Output = Ratio<usize>;
+impl<N> Integrable<usize> for Beat<N>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    type Output = Ratio<N>;
 
     fn integrate_within(&self, self_time: usize, target_time: usize) -> Self::Output {
-        self.0 * (target_time - self_time) as usize
+        self.0.clone() * cast_ratio::<N>(Ratio::from_integer(target_time - self_time))
     }
 
     fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
@@ -28,40 +43,272 @@ impl Integrable<usize> for Beat {
     }
 }
 
+impl<N> CheckedIntegrable<usize> for Beat<N>
+where
+    N: Clone + Integer + FromPrimitive + CheckedAdd + CheckedMul,
+{
+    fn checked_integrate_within(
+        &self,
+        self_time: usize,
+        target_time: usize,
+    ) -> Option<Self::Output> {
+        let measures = Ratio::from_integer(N::from_usize(target_time.checked_sub(self_time)?)?);
+        self.0.checked_mul(&measures)
+    }
+
+    fn checked_accumlate(lhs: Self::Output, rhs: Self::Output) -> Option<Self::Output> {
+        lhs.checked_add(&rhs)
+    }
+}
+
 /// Represents tempo event.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Tempo(pub Ratio<usize>);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tempo<N = usize>(pub Ratio<N>)
+where
+    N: Clone + Integer;
+
+// TODO: make generic over the integer type like `Integrable<Instant> for RhythmChange`.
+impl Integrable<Seconds> for Tempo {
+    /// Elapsed beats.
+    type Output = Ratio<usize>;
+
+    fn integrate_within(&self, self_time: Seconds, target_time: Seconds) -> Self::Output {
+        (target_time - self_time).0 * self.0 / 60
+    }
+
+    fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
+        lhs + rhs
+    }
+
+    fn zero() -> Self::Output {
+        Ratio::zero()
+    }
+}
+
+impl Integrable<Micros> for Tempo {
+    /// Elapsed beats.
+    type Output = Ratio<usize>;
+
+    fn integrate_within(&self, self_time: Micros, target_time: Micros) -> Self::Output {
+        self.integrate_within(Seconds::from(self_time), Seconds::from(target_time))
+    }
+
+    fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
+        lhs + rhs
+    }
+
+    fn zero() -> Self::Output {
+        Ratio::zero()
+    }
+}
 
 /// Represents rhythm change event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RhythmChange<N = usize>(pub Beat<N>, pub Tempo<N>)
+where
+    N: Clone + Integer;
+
+impl<N> RhythmChange<N>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    /// Returns seconds taken by specified measures.
+    fn seconds_of(&self, measures: Ratio<usize>) -> Ratio<N> {
+        let sixty = N::from_usize(60).expect("must be representable");
+        cast_ratio::<N>(measures) * self.0 .0.clone() * sixty / self.1 .0.clone()
+    }
+
+    /// Returns seconds taken by specified measures, or `None` on overflow.
+    fn checked_seconds_of(&self, measures: Ratio<usize>) -> Option<Ratio<N>>
+    where
+        N: CheckedMul,
+    {
+        let measures = Ratio::new_raw(
+            N::from_usize(*measures.numer())?,
+            N::from_usize(*measures.denom())?,
+        );
+        let sixty = Ratio::from_integer(N::from_usize(60)?);
+        measures
+            .checked_mul(&self.0 .0)?
+            .checked_mul(&sixty)?
+            .checked_div(&self.1 .0)
+    }
+}
+
+impl<N> Integrable<Instant> for RhythmChange<N>
+where
+    N: Clone + Integer + FromPrimitive,
+{
+    /// Elapsed seconds.
+    type Output = Ratio<N>;
+
+    fn integrate_within(&self, self_time: Instant, target_time: Instant) -> Self::Output {
+        self.seconds_of(target_time.to_measures() - self_time.to_measures())
+    }
+
+    fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
+        lhs + rhs
+    }
+
+    fn zero() -> Self::Output {
+        Ratio::zero()
+    }
+}
+
+impl<N> CheckedIntegrable<Instant> for RhythmChange<N>
+where
+    N: Clone + Integer + FromPrimitive + CheckedAdd + CheckedMul,
+{
+    fn checked_integrate_within(
+        &self,
+        self_time: Instant,
+        target_time: Instant,
+    ) -> Option<Self::Output> {
+        let measures = target_time
+            .to_measures()
+            .checked_sub(&self_time.to_measures())?;
+        self.checked_seconds_of(measures)
+    }
+
+    fn checked_accumlate(lhs: Self::Output, rhs: Self::Output) -> Option<Self::Output> {
+        lhs.checked_add(&rhs)
+    }
+}
+
+impl Integrable<SignedInstant> for RhythmChange {
+    /// Elapsed seconds from the first section.
+    type Output = Ratio<usize>;
+
+    fn integrate_within(
+        &self,
+        self_time: SignedInstant,
+        target_time: SignedInstant,
+    ) -> Self::Output {
+        let measures = unsigned_ratio(target_time.to_measures() - self_time.to_measures());
+        measures * self.0 .0 * 60 / self.1 .0
+    }
+
+    fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
+        lhs + rhs
+    }
+
+    fn zero() -> Self::Output {
+        Ratio::zero()
+    }
+}
+
+impl CheckedIntegrable<SignedInstant> for RhythmChange {
+    fn checked_integrate_within(
+        &self,
+        self_time: SignedInstant,
+        target_time: SignedInstant,
+    ) -> Option<Self::Output> {
+        let measures = target_time
+            .to_measures()
+            .checked_sub(&self_time.to_measures())?;
+        self.checked_seconds_of(checked_unsigned_ratio(measures)?)
+    }
+
+    fn checked_accumlate(lhs: Self::Output, rhs: Self::Output) -> Option<Self::Output> {
+        lhs.checked_add(&rhs)
+    }
+}
+
+/// Represents stop event, its duration in beats.
+/// Always `usize`; `ChartClock::try_with_stops` casts it into the integer type of the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stop(pub Ratio<usize>);
+
+/// Represents scroll speed event. Negative value scrolls backward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scroll(pub Ratio<isize>);
+
+/// Represents scroll change event.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct RhythmChange(pub Beat, pub Tempo);
+pub struct ScrollChange(pub Beat, pub Scroll);
+
+impl Integrable<Instant> for ScrollChange {
+    /// Scroll position in beats.
+    type Output = Ratio<isize>;
+
+    fn integrate_within(&self, self_time: Instant, target_time: Instant) -> Self::Output {
+        let measures = target_time.to_measures() - self_time.to_measures();
+        signed_ratio(measures * self.0 .0) * self.1 .0
+    }
+
+    fn accumlate(lhs: Self::Output, rhs: Self::Output) -> Self::Output {
+        lhs + rhs
+    }
+
+    fn zero() -> Self::Output {
+        Ratio::zero()
+    }
+}
 
 pub fn merge_beats_and_tempo(
     beats: Timeline<usize, Beat>,
     tempos: Timeline<Instant, Tempo>,
 ) -> Result<Timeline<Instant, RhythmChange>, TimelineError> {
-    let beats: Timeline<_, _> = beats
+    let beats = beats
+        .into_pairs()
+        .map(|(u, b)| (Instant::new_parts(u, 0, 1), b))
+        .collect();
+    merge_beats_with(beats, tempos, Some(Instant::zero()), RhythmChange)
+}
+
+/// Merges beats and tempos which may start before measure 0.
+/// Both must start at the same time, not necessarily at zero.
+pub fn merge_signed_beats_and_tempo(
+    beats: Timeline<isize, Beat>,
+    tempos: Timeline<SignedInstant, Tempo>,
+) -> Result<Timeline<SignedInstant, RhythmChange>, TimelineError> {
+    let beats = beats
+        .into_pairs()
+        .map(|(m, b)| (SignedInstant::new_parts(m, 0, 1), b))
+        .collect();
+    merge_beats_with(beats, tempos, None, RhythmChange)
+}
+
+pub fn merge_beats_and_scroll(
+    beats: Timeline<usize, Beat>,
+    scrolls: Timeline<Instant, Scroll>,
+) -> Result<Timeline<Instant, ScrollChange>, TimelineError> {
+    let beats = beats
         .into_pairs()
         .map(|(u, b)| (Instant::new_parts(u, 0, 1), b))
         .collect();
+    merge_beats_with(beats, scrolls, Some(Instant::zero()), ScrollChange)
+}
 
-    let mut merged_pairs = beats.merge(tempos)?.into_pairs();
-    let Some((must_zero, (Some(first_beat), Some(first_tempo)))) = merged_pairs.next() else {
+/// Merges beats and values aligned at `origin` (or at their first time),
+/// carrying forward the latest of each.
+fn merge_beats_with<U, V, R>(
+    beats: Timeline<U, Beat>,
+    values: Timeline<U, V>,
+    origin: Option<U>,
+    combine: impl Fn(Beat, V) -> R,
+) -> Result<Timeline<U, R>, TimelineError>
+where
+    U: TimeUnit,
+    V: Copy,
+{
+    let mut merged_pairs = beats.merge(values)?.into_pairs();
+    let Some((first_time, (Some(first_beat), Some(first_value)))) = merged_pairs.next() else {
         return Err(TimelineError::NotZeroAligned);
     };
-    if must_zero != Instant::zero() {
+    if origin.is_some_and(|o| o != first_time) {
         return Err(TimelineError::NotZeroAligned);
     }
 
     let mut timeline = Timeline::new();
-    timeline.append(must_zero, RhythmChange(first_beat, first_tempo));
+    timeline.append(first_time, combine(first_beat, first_value));
     let (timeline, _, _) = merged_pairs.fold(
-        (timeline, first_beat, first_tempo),
-        |(mut tl, lb, lt), (i, (b, t))| {
+        (timeline, first_beat, first_value),
+        |(mut tl, lb, lv), (i, (b, v))| {
             let next_beat = b.unwrap_or(lb);
-            let next_tempo = t.unwrap_or(lt);
-            tl.append(i, RhythmChange(next_beat, next_tempo));
-            (tl, next_beat, next_tempo)
+            let next_value = v.unwrap_or(lv);
+            tl.append(i, combine(next_beat, next_value));
+            (tl, next_beat, next_value)
         },
     );
 
@@ -70,8 +317,218 @@ pub fn merge_beats_and_tempo(
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        merge_beats_and_scroll, merge_beats_and_tempo, merge_signed_beats_and_tempo, Beat,
+        RhythmChange, Scroll, Tempo,
+    };
+    use crate::{
+        instant,
+        preintegral::{Preintegral, PreintegralError},
+        signed_instant,
+        time::{Instant, Micros, Seconds},
+        timeline,
+        timeline::{Timeline, TimelineError},
+    };
+
+    use num::{rational::Ratio, BigInt, FromPrimitive};
+
     #[test]
     fn rhythm_change_merge_works() {
+        let beats = timeline! {
+            [0]: Beat(Ratio::new(4, 1)),
+            [2]: Beat(Ratio::new(7, 2)),
+        };
+        let tempos = timeline! {
+            [0:0/1]: Tempo(Ratio::new(120, 1)),
+            [1:1/2]: Tempo(Ratio::new(180, 1)),
+        };
+        let merged: Vec<_> = merge_beats_and_tempo(beats, tempos)
+            .expect("must merge")
+            .into_pairs()
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                (
+                    instant![0:0/1],
+                    RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1)))
+                ),
+                (
+                    instant![1:1/2],
+                    RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(180, 1)))
+                ),
+                (
+                    instant![2:0/1],
+                    RhythmChange(Beat(Ratio::new(7, 2)), Tempo(Ratio::new(180, 1)))
+                ),
+            ],
+            "latest values are carried"
+        );
+
+        let beats = timeline! { [1]: Beat(Ratio::new(4, 1)), };
+        let tempos = timeline! { [0:0/1]: Tempo(Ratio::new(120, 1)), };
+        assert_eq!(
+            merge_beats_and_tempo(beats, tempos).err(),
+            Some(TimelineError::NotZeroAligned),
+            "unaligned timeline is rejected"
+        );
+    }
+
+    #[test]
+    fn signed_rhythm_merge_works() {
+        let beats = timeline! {
+            [-1]: Beat(Ratio::new(4, 1)),
+            [0]: Beat(Ratio::new(3, 1)),
+        };
+        let tempos = timeline! {
+            [signed_instant![-1:0/1]]: Tempo(Ratio::new(120, 1)),
+            [signed_instant![0:1/3]]: Tempo(Ratio::new(60, 1)),
+        };
+        let rhythm = merge_signed_beats_and_tempo(beats, tempos).expect("must merge");
+        assert_eq!(
+            rhythm.times().collect::<Vec<_>>(),
+            vec![
+                signed_instant![-1:0/1],
+                signed_instant![0:0/1],
+                signed_instant![0:1/3]
+            ]
+        );
+
+        let pi = Preintegral::new(rhythm.clone());
+        assert_eq!(pi.fetch(signed_instant![-1:1/2]), Ratio::new(1, 1));
+        assert_eq!(pi.fetch(signed_instant![0:0/1]), Ratio::new(2, 1));
+        assert_eq!(pi.fetch(signed_instant![1:0/1]), Ratio::new(9, 2));
+
+        let checked = Preintegral::try_new(rhythm).expect("must not overflow");
+        assert_eq!(
+            checked.try_fetch(signed_instant![1:0/1]),
+            Ok(Ratio::new(9, 2))
+        );
+        assert_eq!(
+            checked.try_fetch(signed_instant![-2:0/1]),
+            Err(PreintegralError::BeforeStart)
+        );
+
+        let beats = timeline! { [-1]: Beat(Ratio::new(4, 1)), };
+        let tempos = timeline! { [signed_instant![0:0/1]]: Tempo(Ratio::new(120, 1)), };
+        assert_eq!(
+            merge_signed_beats_and_tempo(beats, tempos).err(),
+            Some(TimelineError::NotZeroAligned),
+            "unaligned timeline is rejected"
+        );
+    }
+
+    #[test]
+    fn wall_clock_tempo_integration_works() {
+        let tempos = timeline! {
+            [Seconds(Ratio::new(0, 1))]: Tempo(Ratio::new(120, 1)),
+            [Seconds(Ratio::new(3, 2))]: Tempo(Ratio::new(90, 1)),
+        };
+        let pi = Preintegral::new(tempos);
+        assert_eq!(pi.fetch(Seconds(Ratio::new(1, 1))), Ratio::new(2, 1));
+        assert_eq!(pi.fetch(Seconds(Ratio::new(5, 2))), Ratio::new(9, 2));
+
+        let tempos = timeline! {
+            [Micros(0)]: Tempo(Ratio::new(120, 1)),
+            [Micros(1_500_000)]: Tempo(Ratio::new(90, 1)),
+        };
+        let pi = Preintegral::new(tempos);
+        assert_eq!(pi.fetch(Micros(2_500_000)), Ratio::new(9, 2));
+
+        let keysounds = timeline! {
+            [Micros(0)]: 'a',
+            [Micros(500)]: 'c',
+        };
+        let inputs = timeline! {
+            [Micros(250)]: 'b',
+            [Micros(500)]: 'd',
+        };
+        let merged: Vec<_> = keysounds.merge(inputs).unwrap().into_pairs().collect();
+        assert_eq!(
+            merged,
+            vec![
+                (Micros(0), (Some('a'), None)),
+                (Micros(250), (None, Some('b'))),
+                (Micros(500), (Some('c'), Some('d'))),
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_integration_works() {
+        let beats = timeline! {
+            [0]: Beat(Ratio::new(4, 1)),
+            [1]: Beat(Ratio::new(3, 1)),
+        };
+        let scrolls = timeline! {
+            [0:0/1]: Scroll(Ratio::new(1, 1)),
+            [0:1/2]: Scroll(Ratio::new(2, 1)),
+            [1:2/3]: Scroll(Ratio::new(-1, 2)),
+        };
+        let pi = Preintegral::new(merge_beats_and_scroll(beats, scrolls).expect("must merge"));
+
+        assert_eq!(pi.fetch(instant![0:1/2]), Ratio::new(2, 1));
+        assert_eq!(pi.fetch(instant![1:0/1]), Ratio::new(6, 1));
+        assert_eq!(pi.fetch(instant![1:2/3]), Ratio::new(10, 1));
+        assert_eq!(pi.fetch(instant![2:0/1]), Ratio::new(19, 2));
+    }
+
+    #[test]
+    fn checked_rhythm_integration_works() {
+        // coprime tempos make the denominator of elapsed seconds grow quickly
+        const PRIMES: [usize; 12] = [
+            1601, 1607, 1609, 1613, 1619, 1621, 1627, 1637, 1657, 1663, 1667, 1669,
+        ];
+        let narrow: Timeline<Instant, RhythmChange> = PRIMES
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                (
+                    Instant::new_parts(i, 0, 1),
+                    RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(p, 10))),
+                )
+            })
+            .collect();
+        let wide: Timeline<Instant, RhythmChange<BigInt>> = PRIMES
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let four = BigInt::from_usize(4).unwrap();
+                let tempo = Ratio::new(
+                    BigInt::from_usize(p).unwrap(),
+                    BigInt::from_usize(10).unwrap(),
+                );
+                (
+                    Instant::new_parts(i, 0, 1),
+                    RhythmChange(Beat(Ratio::from_integer(four)), Tempo(tempo)),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            Preintegral::try_new(narrow).unwrap_err(),
+            PreintegralError::Overflow
+        );
+
+        let wide = Preintegral::try_new(wide).expect("big integers never overflow");
+        let expected = PRIMES[..11]
+            .iter()
+            .map(|&p| Ratio::new(BigInt::from(2400), BigInt::from_usize(p).unwrap()))
+            .fold(Ratio::from_integer(BigInt::from(0)), |acc, s| acc + s);
+        assert_eq!(wide.fetch(instant![11:0/1]), expected);
+        assert_eq!(
+            wide.try_fetch(instant![11:0/1]),
+            Ok(expected),
+            "checked fetch agrees"
+        );
 
+        let beats: Timeline<usize, Beat> = timeline! { [2]: Beat(Ratio::new(4, 1)), };
+        let beats = Preintegral::try_new(beats).unwrap();
+        assert_eq!(beats.try_fetch(3), Ok(Ratio::new(4, 1)));
+        assert_eq!(
+            beats.try_fetch(1),
+            Err(PreintegralError::BeforeStart),
+            "fails instead of underflowing"
+        );
     }
 }
@@ -0,0 +1,274 @@
+//! Contains visibility interval solver for non-monotonic scroll.
+
+use crate::{
+    chart::Chart,
+    note::Note,
+    preintegral::Preintegral,
+    render::{RenderSettings, RenderedNote},
+    span::SpanTimeline,
+    time::Instant,
+    util::{signed_ratio, unsigned_ratio},
+    value::ScrollChange,
+};
+
+use std::cmp::Ordering;
+
+use num::{rational::Ratio, ToPrimitive, Zero};
+
+/// Computes every interval of current time in which the note is on screen.
+///
+/// Intervals are half-open `[enter, leave)`, sorted and never adjacent.
+/// A note stays visible until its time (or its end for hold notes) at latest.
+pub fn visibility_intervals(
+    scroll: &Preintegral<Instant, ScrollChange>,
+    settings: RenderSettings,
+    time: Instant,
+    note: &Note,
+) -> Vec<(Instant, Instant)> {
+    let until = note.hold_end().unwrap_or(time);
+    let head = scroll.fetch(time);
+    let tail = note.hold_end().map_or(head, |end| scroll.fetch(end));
+    let window = signed_ratio(settings.lane_length / settings.hispeed);
+
+    position_intervals(scroll, head.min(tail) - window, head.max(tail), until)
+}
+
+/// Computes intervals before `until` in which scroll position is within `[lower, upper]`.
+fn position_intervals(
+    scroll: &Preintegral<Instant, ScrollChange>,
+    lower: Ratio<isize>,
+    upper: Ratio<isize>,
+    until: Instant,
+) -> Vec<(Instant, Instant)> {
+    let mut intervals: Vec<(Instant, Instant)> = vec![];
+    let mut sections = scroll.sections().peekable();
+    while let Some((start, change, base)) = sections.next() {
+        if start >= until {
+            break;
+        }
+        let end = match sections.peek() {
+            Some((next, _, _)) if *next < until => *next,
+            _ => until,
+        };
+
+        let slope = signed_ratio(change.0 .0) * change.1 .0;
+        let start_measures = signed_ratio(start.to_measures());
+        let end_measures = signed_ratio(end.to_measures());
+        let solve = |position: Ratio<isize>| {
+            let measures = start_measures + (position - base) / slope;
+            Instant::from_measures(unsigned_ratio(
+                measures.max(start_measures).min(end_measures),
+            ))
+        };
+        let (enter, leave) = match slope.cmp(&Ratio::zero()) {
+            Ordering::Equal if lower <= *base && *base <= upper => (start, end),
+            Ordering::Equal => continue,
+            Ordering::Greater => (solve(lower), solve(upper)),
+            Ordering::Less => (solve(upper), solve(lower)),
+        };
+        if enter >= leave {
+            continue;
+        }
+
+        match intervals.last_mut() {
+            Some((_, last_leave)) if *last_leave == enter => *last_leave = leave,
+            _ => intervals.push((enter, leave)),
+        }
+    }
+
+    intervals
+}
+
+/// Indexes visibility intervals of all notes in chart for culling.
+#[derive(Debug, Clone)]
+pub struct VisibilityIndex {
+    settings: RenderSettings,
+    spans: SpanTimeline<Instant, (Instant, Note)>,
+}
+
+impl VisibilityIndex {
+    /// Solves visibility intervals of all rendered notes.
+    pub fn new(
+        chart: &Chart,
+        scroll: &Preintegral<Instant, ScrollChange>,
+        settings: RenderSettings,
+    ) -> VisibilityIndex {
+        let spans = chart
+            .notes()
+            .filter(|(_, note)| note.is_rendered())
+            .flat_map(|(time, note)| {
+                visibility_intervals(scroll, settings, time, note)
+                    .into_iter()
+                    .map(move |(enter, leave)| (enter, leave, (time, *note)))
+            })
+            .collect();
+
+        VisibilityIndex { settings, spans }
+    }
+
+    /// Returns indexed intervals, valued by note time and note.
+    pub fn spans(&self) -> &SpanTimeline<Instant, (Instant, Note)> {
+        &self.spans
+    }
+
+    /// Fills `buffer` with notes visible at `now`, looking up only the indexed intervals.
+    /// Positions are clipped into the lane.
+    pub fn render(
+        &self,
+        scroll: &Preintegral<Instant, ScrollChange>,
+        now: Instant,
+        buffer: &mut Vec<RenderedNote>,
+    ) {
+        buffer.clear();
+
+        let origin = scroll.fetch(now);
+        let scale = signed_ratio(self.settings.hispeed / self.settings.lane_length);
+        let clipped_y = |time: Instant| {
+            let y = ((scroll.fetch(time) - origin) * scale)
+                .max(Ratio::zero())
+                .min(Ratio::from_integer(1));
+            y.to_f64().expect("must be representable")
+        };
+
+        self.spans.for_each_active_at(now, |_, _, (time, note)| {
+            buffer.push(RenderedNote {
+                lane: note.lane,
+                time: *time,
+                note: *note,
+                y: clipped_y(*time),
+                end_y: note.hold_end().map(clipped_y),
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{visibility_intervals, VisibilityIndex};
+    use crate::{
+        chart::Chart,
+        instant,
+        note::{LaneId, Note, NoteKind},
+        preintegral::Preintegral,
+        render::{render_notes, RenderSettings},
+        time::Instant,
+        timeline,
+        value::{merge_beats_and_scroll, Beat, RhythmChange, Scroll, Tempo},
+    };
+
+    use num::rational::Ratio;
+
+    #[test]
+    fn reverse_scroll_intervals_work() {
+        // position: 0 -> 4 (0:0 - 1:0), 4 -> 2 (1:0 - 1:1/2), 2 -> 6 (1:1/2 - 2:1/2)
+        let beats = timeline! { [0]: Beat(Ratio::new(4, 1)), };
+        let scrolls = timeline! {
+            [0:0/1]: Scroll(Ratio::new(1, 1)),
+            [1:0/1]: Scroll(Ratio::new(-1, 1)),
+            [1:1/2]: Scroll(Ratio::new(1, 1)),
+        };
+        let scroll = Preintegral::new(merge_beats_and_scroll(beats, scrolls).unwrap());
+        let settings = RenderSettings {
+            hispeed: Ratio::new(1, 1),
+            lane_length: Ratio::new(1, 1),
+        };
+
+        // note at position 4, visible while position is within [3, 4]
+        let note = Note::new(LaneId(0), NoteKind::Tap);
+        assert_eq!(
+            visibility_intervals(&scroll, settings, instant![2:0/1], &note),
+            vec![
+                (instant![0:3/4], instant![1:1/4]),
+                (instant![1:3/4], instant![2:0/1]),
+            ],
+            "note enters twice"
+        );
+
+        // note at position 1 is judged before reverse scroll
+        assert_eq!(
+            visibility_intervals(&scroll, settings, instant![0:1/4], &note),
+            vec![(instant![0:0/1], instant![0:1/4])],
+            "intervals end at note time"
+        );
+
+        let mut chart = Chart::new(
+            timeline! {
+                [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+            },
+            1,
+        );
+        chart.insert_note(instant![2:0/1], note).unwrap();
+        chart
+            .insert_note(instant![1:0/1], Note::new(LaneId(0), NoteKind::Invisible))
+            .unwrap();
+        let index = VisibilityIndex::new(&chart, &scroll, settings);
+        assert_eq!(index.spans().len(), 2, "invisible note is not indexed");
+
+        let mut buffer = vec![];
+        index.render(&scroll, instant![1:1/8], &mut buffer);
+        assert_eq!(buffer.len(), 1, "visible while reversing");
+        assert_eq!(buffer[0].y, 0.5);
+        index.render(&scroll, instant![1:1/2], &mut buffer);
+        assert!(buffer.is_empty(), "culled after leaving");
+    }
+
+    #[test]
+    fn index_matches_render_list() {
+        let beats = timeline! { [0]: Beat(Ratio::new(4, 1)), };
+        let scrolls = timeline! {
+            [0:0/1]: Scroll(Ratio::new(1, 1)),
+            [1:1/4]: Scroll(Ratio::new(2, 1)),
+        };
+        let scroll = Preintegral::new(merge_beats_and_scroll(beats, scrolls).unwrap());
+        let settings = RenderSettings {
+            hispeed: Ratio::new(1, 1),
+            lane_length: Ratio::new(2, 1),
+        };
+        let mut chart = Chart::new(
+            timeline! {
+                [0:0/1]: RhythmChange(Beat(Ratio::new(4, 1)), Tempo(Ratio::new(120, 1))),
+            },
+            2,
+        );
+        for time in [
+            instant![0:1/2],
+            instant![1:0/1],
+            instant![1:1/2],
+            instant![2:0/1],
+        ] {
+            chart
+                .insert_note(time, Note::new(LaneId(0), NoteKind::Tap))
+                .unwrap();
+        }
+        let hold = NoteKind::Hold {
+            end: instant![2:1/4],
+        };
+        chart
+            .insert_note(instant![1:0/1], Note::new(LaneId(1), hold))
+            .unwrap();
+        chart
+            .insert_note(instant![0:3/4], Note::new(LaneId(1), NoteKind::Invisible))
+            .unwrap();
+
+        let index = VisibilityIndex::new(&chart, &scroll, settings);
+        let (mut listed, mut indexed) = (vec![], vec![]);
+        // every sixteenth, including the exact note times and the hold end
+        for i in 0..48 {
+            let now = Instant::new_parts(i / 16, i % 16, 16);
+            render_notes(&chart, &scroll, settings, now, &mut listed);
+            index.render(&scroll, now, &mut indexed);
+            for buffer in [&mut listed, &mut indexed] {
+                buffer.sort_by_key(|r| (r.lane, r.time));
+            }
+            assert_eq!(listed, indexed, "at {:?}", now);
+        }
+
+        render_notes(&chart, &scroll, settings, instant![1:0/1], &mut listed);
+        let lanes: Vec<_> = listed.iter().map(|r| (r.lane.0, r.time)).collect();
+        assert_eq!(
+            lanes,
+            vec![(1, instant![1:0/1])],
+            "tap at now has passed, hold at now is held"
+        );
+    }
+}